@@ -0,0 +1,246 @@
+use crate::deadlock::DeadlockDetector;
+use crate::events::Event;
+use crate::solver::Board;
+use hecs::Entity;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct Position {
+    pub x: u16,
+    pub y: u16,
+    pub z: u8,
+}
+
+/// Size of the level currently loaded, in tiles.
+#[derive(Clone, Copy, Default)]
+pub struct MapSize {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// What an entity looks like, as the names of the textures to draw for it.
+/// Frontends map those names to whatever they draw with.
+pub struct Renderable {
+    textures: Vec<String>,
+}
+
+pub enum RenderableKind {
+    Static,
+    Animated,
+}
+
+impl Renderable {
+    pub fn new_static(texture: &str) -> Self {
+        Self {
+            textures: vec![texture.to_string()],
+        }
+    }
+
+    pub fn new_animated(paths: Vec<String>) -> Self {
+        Self {
+            textures: paths.into_iter().collect(),
+        }
+    }
+
+    pub fn kind(&self) -> RenderableKind {
+        match self.textures.len() {
+            0 => panic!("invalid renderable"),
+            1 => RenderableKind::Static,
+            _ => RenderableKind::Animated,
+        }
+    }
+
+    pub fn path(&self, path_index: usize) -> &str {
+        // If we get asked for a path that is larger than the
+        // number of paths we actually have, we simply mod the index
+        // with the length to get an index that is in range.
+        &self.textures[path_index % self.textures.len()]
+    }
+}
+
+pub struct Wall {}
+
+pub struct Player {}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BoxColour {
+    Red,
+    Blue,
+}
+
+impl Display for BoxColour {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            BoxColour::Red => "red",
+            BoxColour::Blue => "blue",
+        })?;
+        Ok(())
+    }
+}
+
+pub struct Box {
+    pub colour: BoxColour,
+}
+
+pub struct BoxSpot {
+    pub colour: BoxColour,
+}
+
+pub struct Movable;
+
+/// Where an entity was when the level started.
+pub struct Spawn {
+    pub position: Position,
+}
+
+pub struct Immovable;
+
+#[derive(Default)]
+pub enum GameplayState {
+    #[default]
+    Playing,
+    Won,
+    // The level can't be solved from here anymore
+    Lost,
+}
+
+impl Display for GameplayState {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            GameplayState::Playing => "Playing",
+            GameplayState::Won => "Won",
+            GameplayState::Lost => "Lost",
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Gameplay {
+    pub state: GameplayState,
+    pub moves_count: u32,
+    // Moves that pushed at least one box
+    pub pushes_count: u32,
+    // Time spent playing the level, from the first move on
+    pub elapsed: Duration,
+    pub boxes_on_spot: usize,
+    pub boxes_total: usize,
+    // Whether the boxes were last moved into a deadlock
+    pub position_lost: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// The step in LURD notation, uppercase when a box was pushed.
+    pub fn to_lurd(self, pushed: bool) -> char {
+        let lurd = match self {
+            Direction::Left => 'l',
+            Direction::Up => 'u',
+            Direction::Right => 'r',
+            Direction::Down => 'd',
+        };
+
+        if pushed {
+            lurd.to_ascii_uppercase()
+        } else {
+            lurd
+        }
+    }
+}
+
+impl Display for Direction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        })?;
+        Ok(())
+    }
+}
+
+/// A single step of the player, with everything it moved and where it was
+/// before so the step can be taken back.
+pub struct Step {
+    pub direction: Direction,
+    pub moved: Vec<(Entity, Position)>,
+    pub pushed: bool,
+    // Part of a longer move, undone and redone along with the step before
+    pub follows_previous: bool,
+}
+
+#[derive(Default)]
+pub struct History {
+    pub undo: Vec<Step>,
+    pub redo: Vec<Step>,
+}
+
+impl History {
+    /// Every step taken so far, minus the undone ones, in LURD notation.
+    pub fn to_lurd(&self) -> String {
+        self.undo
+            .iter()
+            .map(|step| step.direction.to_lurd(step.pushed))
+            .collect()
+    }
+}
+
+/// The rules of the Sokoban variant being played, set by each level.
+#[derive(Clone, Copy, Debug)]
+pub struct Rules {
+    // How many boxes in a row the player can push at once, `None` for any
+    pub max_pushed_boxes: Option<usize>,
+}
+
+impl Default for Rules {
+    /// Classic Sokoban, one box at a time.
+    fn default() -> Self {
+        Self {
+            max_pushed_boxes: Some(1),
+        }
+    }
+}
+
+impl Rules {
+    pub fn can_push(&self, boxes: usize) -> bool {
+        self.max_pushed_boxes.is_none_or(|max| boxes <= max)
+    }
+}
+
+/// Tiles of the current level a box of some colour can never be pushed to a
+/// spot of its colour from.
+#[derive(Default)]
+pub struct DeadSquares {
+    pub squares: HashSet<(u16, u16, BoxColour)>,
+}
+
+impl DeadSquares {
+    pub fn contains(&self, position: &Position, colour: BoxColour) -> bool {
+        self.squares.contains(&(position.x, position.y, colour))
+    }
+}
+
+/// The walls and spots of the current level and the deadlock detector built
+/// from them, kept so positions can be checked without reading the level
+/// again.
+#[derive(Default)]
+pub struct Deadlocks {
+    pub board: Board,
+    pub detector: DeadlockDetector,
+}
+
+#[derive(Default)]
+pub struct EventQueue {
+    pub events: Vec<Event>,
+}
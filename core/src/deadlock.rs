@@ -0,0 +1,435 @@
+//! Finds positions a level can no longer be solved from.
+//!
+//! Three kinds of deadlock are recognised:
+//! - dead squares, tiles a box of some colour can never be pushed to a spot of
+//!   its colour from, whatever the other boxes do;
+//! - freezes, boxes off their spot that block each other so none of them can
+//!   ever move again;
+//! - corrals, areas the player is shut out of whose boxes can't all be put on
+//!   spots anymore. Proving this takes a small search limited to those boxes.
+
+use crate::components::*;
+use crate::solver::{
+    opposite, state_from_world, Board, BoardState, Solver, SolverBudget, SolverStatus, DIRECTIONS,
+};
+use hecs::World;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Distance of cells a box can never be pushed to a spot from.
+pub const UNREACHABLE: usize = usize::MAX;
+
+/// Positions the search for a corral deadlock may look at before giving up
+/// and assuming the corral can be solved.
+pub const CORRAL_SEARCH_NODES: usize = 128;
+
+const AXES: [[Direction; 2]; 2] = [
+    [Direction::Up, Direction::Down],
+    [Direction::Left, Direction::Right],
+];
+
+/// Fewest pushes needed to get a box of `colour` from each cell to one of
+/// the spots of that colour, ignoring every other box. Found by pulling boxes
+/// away from the spots: a box can be pulled from a cell when both the next
+/// cell and the one after it, where the player stands, are free.
+pub fn push_distances(board: &Board, colour: BoxColour) -> Vec<usize> {
+    let mut distances = vec![UNREACHABLE; board.cell_count()];
+    let mut to_visit = VecDeque::new();
+
+    for cell in (0..board.cell_count()).filter(|cell| board.spot(*cell) == Some(colour)) {
+        distances[cell] = 0;
+        to_visit.push_back(cell);
+    }
+
+    while let Some(cell) = to_visit.pop_front() {
+        for direction in DIRECTIONS {
+            let pulled_to = board.open_neighbour(cell, direction);
+            let player = pulled_to.and_then(|next| board.open_neighbour(next, direction));
+            if let (Some(pulled_to), Some(_)) = (pulled_to, player) {
+                if distances[pulled_to] == UNREACHABLE {
+                    distances[pulled_to] = distances[cell] + 1;
+                    to_visit.push_back(pulled_to);
+                }
+            }
+        }
+    }
+
+    distances
+}
+
+/// The floor tiles a box of each colour gets stuck on, like corners and
+/// walls without a spot of its colour along them.
+pub fn find_dead_squares(board: &Board) -> DeadSquares {
+    let mut squares = HashSet::new();
+
+    for colour in [BoxColour::Red, BoxColour::Blue] {
+        let distances = push_distances(board, colour);
+        for (cell, distance) in distances.into_iter().enumerate() {
+            if distance == UNREACHABLE && !board.is_wall(cell) {
+                let (x, y) = board.coordinates(cell);
+                squares.insert((x, y, colour));
+            }
+        }
+    }
+
+    DeadSquares { squares }
+}
+
+/// Looks for deadlocks in the positions of one level.
+#[derive(Clone, Default)]
+pub struct DeadlockDetector {
+    // Fewest pushes needed to get a box of each colour from a cell to a spot
+    // of its colour, ignoring the other boxes
+    distances: HashMap<BoxColour, Vec<usize>>,
+}
+
+impl DeadlockDetector {
+    pub fn new(board: &Board) -> Self {
+        let distances = [BoxColour::Red, BoxColour::Blue]
+            .into_iter()
+            .map(|colour| (colour, push_distances(board, colour)))
+            .collect();
+
+        Self { distances }
+    }
+
+    /// Fewest pushes needed to get a box of `colour` from `cell` to a spot of
+    /// its colour, `UNREACHABLE` on a dead square.
+    pub fn push_distance(&self, cell: usize, colour: BoxColour) -> usize {
+        self.distances[&colour][cell]
+    }
+
+    pub fn is_dead_square(&self, cell: usize, colour: BoxColour) -> bool {
+        self.push_distance(cell, colour) == UNREACHABLE
+    }
+
+    /// Whether any box is on a dead square, or frozen off a spot of its
+    /// colour, or shut in a corral that can't be solved.
+    pub fn is_deadlocked(&self, board: &Board, rules: &Rules, state: &BoardState) -> bool {
+        let cells: Vec<usize> = state.boxes.iter().map(|(cell, _)| *cell).collect();
+
+        state
+            .boxes
+            .iter()
+            .any(|(cell, colour)| self.is_dead_square(*cell, *colour))
+            || self.is_freeze_deadlock(board, rules, state, &cells)
+            || self.is_corral_deadlock(board, rules, state)
+    }
+
+    /// Whether one of the boxes at `cells`, or a box next to them, can never
+    /// move again while off a spot of its colour. Checking the boxes that just
+    /// moved is enough to catch a freeze as soon as it happens.
+    pub fn is_freeze_deadlock(
+        &self,
+        board: &Board,
+        rules: &Rules,
+        state: &BoardState,
+        cells: &[usize],
+    ) -> bool {
+        let mut to_check: Vec<usize> = cells.to_vec();
+        for cell in cells {
+            for direction in DIRECTIONS {
+                if let Some(neighbour) = board.neighbour(*cell, direction) {
+                    to_check.push(neighbour);
+                }
+            }
+        }
+
+        to_check.into_iter().any(|cell| match state.box_at(cell) {
+            Some(colour) => {
+                board.spot(cell) != Some(colour)
+                    && self.is_frozen(board, rules, state, cell, &mut Vec::new())
+            }
+            None => false,
+        })
+    }
+
+    /// Whether the box at `cell` can never move again. The boxes in `fixed`
+    /// are being checked further up and count as walls, which is how boxes
+    /// that block each other are found.
+    fn is_frozen(
+        &self,
+        board: &Board,
+        rules: &Rules,
+        state: &BoardState,
+        cell: usize,
+        fixed: &mut Vec<usize>,
+    ) -> bool {
+        fixed.push(cell);
+        let frozen = AXES
+            .iter()
+            .all(|axis| self.is_blocked(board, rules, state, cell, axis, fixed));
+        fixed.pop();
+
+        frozen
+    }
+
+    /// Whether the box at `cell` can never move along `axis`.
+    fn is_blocked(
+        &self,
+        board: &Board,
+        rules: &Rules,
+        state: &BoardState,
+        cell: usize,
+        axis: &[Direction; 2],
+        fixed: &mut Vec<usize>,
+    ) -> bool {
+        let colour = state.box_at(cell).unwrap();
+        let neighbours = axis.map(|direction| board.open_neighbour(cell, direction));
+        // When boxes can be pushed in a row, a box next to this one can be
+        // pushed along with it and never blocks it
+        let boxes_block = !rules.can_push(2);
+
+        // A wall on either side, there is nowhere for the player to push from
+        if neighbours.iter().any(|neighbour| {
+            neighbour.is_none_or(|neighbour| boxes_block && fixed.contains(&neighbour))
+        }) {
+            return true;
+        }
+
+        // Either way leads to a dead square
+        let neighbours = neighbours.map(Option::unwrap);
+        if neighbours
+            .iter()
+            .all(|neighbour| self.is_dead_square(*neighbour, colour))
+        {
+            return true;
+        }
+
+        // A box on either side that can't move either
+        boxes_block
+            && neighbours.iter().any(|neighbour| {
+                state.box_at(*neighbour).is_some()
+                    && self.is_frozen(board, rules, state, *neighbour, fixed)
+            })
+    }
+
+    /// Whether the player is shut out of an area whose boxes can't all be put
+    /// on spots anymore. Only areas where every push the player can make on
+    /// their boxes goes further in are checked, the player can work on the
+    /// others from outside.
+    pub fn is_corral_deadlock(&self, board: &Board, rules: &Rules, state: &BoardState) -> bool {
+        let reachable = state.reachable(board);
+        let mut in_corral = vec![false; board.cell_count()];
+
+        for start in 0..board.cell_count() {
+            if reachable[start]
+                || in_corral[start]
+                || board.is_wall(start)
+                || state.box_at(start).is_some()
+            {
+                continue;
+            }
+
+            let (corral, boxes) = find_corral(board, state, start);
+            for cell in corral.iter() {
+                in_corral[*cell] = true;
+            }
+
+            if boxes.len() == state.boxes.len()
+                || boxes
+                    .iter()
+                    .all(|(cell, colour)| board.spot(*cell) == Some(*colour))
+            {
+                continue;
+            }
+
+            let can_push_out = boxes.iter().any(|(cell, _)| {
+                DIRECTIONS.into_iter().any(|direction| {
+                    let from = board.neighbour(*cell, opposite(direction));
+                    let to = board.open_neighbour(*cell, direction);
+                    match (from, to) {
+                        (Some(from), Some(to)) => {
+                            reachable[from]
+                                && !corral.contains(&to)
+                                && state.push_length(board, rules, *cell, direction).is_some()
+                        }
+                        _ => false,
+                    }
+                })
+            });
+            if can_push_out {
+                continue;
+            }
+
+            // Leaving out every other box only makes things easier, so if
+            // the corral's boxes can't be solved on their own they can't be
+            // solved at all
+            let corral_state = BoardState::new(state.player, boxes);
+            let mut solver =
+                Solver::with_deadlocks(board.clone(), corral_state, *rules, self.clone(), false);
+            let budget = SolverBudget {
+                max_nodes: Some(CORRAL_SEARCH_NODES),
+                max_time: None,
+            };
+            if matches!(solver.solve(budget), SolverStatus::Unsolvable) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// The free cells connected to `start` and the boxes around them.
+fn find_corral(
+    board: &Board,
+    state: &BoardState,
+    start: usize,
+) -> (HashSet<usize>, Vec<(usize, BoxColour)>) {
+    let mut corral = HashSet::from([start]);
+    let mut boxes = Vec::new();
+    let mut to_visit = vec![start];
+
+    while let Some(cell) = to_visit.pop() {
+        for direction in DIRECTIONS {
+            let Some(next) = board.open_neighbour(cell, direction) else {
+                continue;
+            };
+            match state.box_at(next) {
+                Some(colour) => {
+                    if !boxes.contains(&(next, colour)) {
+                        boxes.push((next, colour));
+                    }
+                }
+                None => {
+                    if corral.insert(next) {
+                        to_visit.push(next);
+                    }
+                }
+            }
+        }
+    }
+
+    (corral, boxes)
+}
+
+/// Whether the position of the level loaded in `world` can't be solved
+/// anymore.
+pub fn is_position_lost(world: &World) -> bool {
+    let mut query = world.query::<&Deadlocks>();
+    let deadlocks = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&Rules>();
+    let rules = query.iter().next().unwrap().1;
+
+    let state = state_from_world(world, &deadlocks.board);
+    deadlocks
+        .detector
+        .is_deadlocked(&deadlocks.board, rules, &state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::MapFormat;
+    use crate::solver::board_from_world;
+    use crate::solver::tests::load;
+
+    fn detect(map: &str, rules: Rules) -> (Board, BoardState, DeadlockDetector) {
+        let world = load(map, MapFormat::Xsb, rules);
+        let (board, state) = board_from_world(&world);
+        let detector = DeadlockDetector::new(&board);
+        (board, state, detector)
+    }
+
+    fn box_cells(state: &BoardState) -> Vec<usize> {
+        state.boxes.iter().map(|(cell, _)| *cell).collect()
+    }
+
+    #[test]
+    fn box_in_a_corner_is_on_a_dead_square() {
+        let world = load(
+            "
+                ######
+                #$   #
+                #    #
+                #@  .#
+                ######
+            ",
+            MapFormat::Xsb,
+            Rules::default(),
+        );
+        let (board, state) = board_from_world(&world);
+        let detector = DeadlockDetector::new(&board);
+
+        let (cell, colour) = state.boxes[0];
+        assert!(detector.is_dead_square(cell, colour));
+        assert!(is_position_lost(&world));
+    }
+
+    #[test]
+    fn square_of_boxes_is_frozen() {
+        let (board, state, detector) = detect(
+            "
+                ########
+                #      #
+                #  $$  #
+                #  $$  #
+                #@     #
+                #  ..  #
+                #  ..  #
+                ########
+            ",
+            Rules::default(),
+        );
+
+        assert!(!state
+            .boxes
+            .iter()
+            .any(|(cell, colour)| detector.is_dead_square(*cell, *colour)));
+        assert!(detector.is_freeze_deadlock(&board, &Rules::default(), &state, &box_cells(&state)));
+        assert!(detector.is_deadlocked(&board, &Rules::default(), &state));
+    }
+
+    #[test]
+    fn boxes_shut_in_a_corral_are_deadlocked() {
+        // The player can't get between the boxes in the corridor anymore, the
+        // only push left moves the first one against the second one
+        let (board, state, detector) = detect(
+            "
+                ##########
+                #    #####
+                # $. #####
+                #@  $ $..#
+                ##########
+            ",
+            Rules::default(),
+        );
+
+        assert!(!state
+            .boxes
+            .iter()
+            .any(|(cell, colour)| detector.is_dead_square(*cell, *colour)));
+        assert!(!detector.is_freeze_deadlock(
+            &board,
+            &Rules::default(),
+            &state,
+            &box_cells(&state)
+        ));
+        assert!(detector.is_corral_deadlock(&board, &Rules::default(), &state));
+    }
+
+    #[test]
+    fn train_of_boxes_is_not_frozen() {
+        // Two boxes side by side against a wall can only be moved by pushing
+        // both at once
+        let map = "
+            ########
+            #  $$..#
+            #      #
+            #@     #
+            ########
+        ";
+        let classic = Rules::default();
+        let trains = Rules {
+            max_pushed_boxes: Some(2),
+        };
+
+        let (board, state, detector) = detect(map, classic);
+        assert!(detector.is_freeze_deadlock(&board, &classic, &state, &box_cells(&state)));
+
+        let (board, state, detector) = detect(map, trains);
+        assert!(!detector.is_freeze_deadlock(&board, &trains, &state, &box_cells(&state)));
+        assert!(!detector.is_deadlocked(&board, &trains, &state));
+    }
+}
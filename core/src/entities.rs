@@ -0,0 +1,81 @@
+use crate::components::*;
+use hecs::{Entity, World};
+
+pub fn create_wall(world: &mut World, position: Position) -> Entity {
+    world.spawn((
+        Position { z: 10, ..position },
+        Renderable::new_static("wall"),
+        Wall {},
+        Immovable {},
+    ))
+}
+
+pub fn create_floor(world: &mut World, position: Position) -> Entity {
+    world.spawn((
+        Position { z: 5, ..position },
+        Renderable::new_static("floor"),
+    ))
+}
+
+pub fn create_box(world: &mut World, position: Position, colour: BoxColour) -> Entity {
+    world.spawn((
+        Position { z: 10, ..position },
+        Renderable::new_animated(vec![
+            format!("box_{}_1", colour),
+            format!("box_{}_2", colour),
+        ]),
+        Box { colour },
+        Movable {},
+        Spawn { position },
+    ))
+}
+
+pub fn create_box_spot(world: &mut World, position: Position, colour: BoxColour) -> Entity {
+    world.spawn((
+        Position { z: 9, ..position },
+        Renderable::new_static(&format!("box_spot_{}", colour)),
+        BoxSpot { colour },
+    ))
+}
+
+pub fn create_player(world: &mut World, position: Position) -> Entity {
+    world.spawn((
+        Position { z: 10, ..position },
+        Renderable::new_animated(vec![
+            String::from("player_1"),
+            String::from("player_2"),
+            String::from("player_3"),
+        ]),
+        Player {},
+        Movable {},
+        Spawn { position },
+    ))
+}
+
+pub fn create_gameplay(world: &mut World) -> Entity {
+    world.spawn((Gameplay::default(),))
+}
+
+pub fn create_map_size(world: &mut World) -> Entity {
+    world.spawn((MapSize::default(),))
+}
+
+pub fn create_history(world: &mut World) -> Entity {
+    world.spawn((History::default(),))
+}
+
+pub fn create_rules(world: &mut World) -> Entity {
+    world.spawn((Rules::default(),))
+}
+
+pub fn create_event_queue(world: &mut World) -> Entity {
+    world.spawn((EventQueue::default(),))
+}
+
+pub fn create_dead_squares(world: &mut World) -> Entity {
+    world.spawn((DeadSquares::default(),))
+}
+
+pub fn create_deadlocks(world: &mut World) -> Entity {
+    world.spawn((Deadlocks::default(),))
+}
@@ -10,6 +10,11 @@ pub struct BoxPlacedOnSpot {
     pub is_correct_spot: bool,
 }
 
+#[derive(Debug)]
+pub struct BoxStuck {
+    pub entity: Entity,
+}
+
 #[derive(Debug)]
 pub enum Event {
     // Fired when the player hits an obstacle like a wall
@@ -20,4 +25,7 @@ pub enum Event {
 
     // Fired when the box is placed on a spot
     BoxPlacedOnSpot(BoxPlacedOnSpot),
-}
\ No newline at end of file
+
+    // Fired when a box is pushed where it can't reach a spot of its colour
+    BoxStuck(BoxStuck),
+}
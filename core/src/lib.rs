@@ -0,0 +1,14 @@
+//! The game itself, without a window: the level model, the move rules and
+//! win detection. Frontends drive it by stepping the player and drawing the
+//! world, nothing in here depends on graphics or audio.
+
+pub mod components;
+pub mod deadlock;
+pub mod entities;
+pub mod events;
+pub mod lurd;
+pub mod map;
+pub mod progress;
+pub mod save;
+pub mod solver;
+pub mod systems;
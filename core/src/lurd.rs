@@ -0,0 +1,16 @@
+use crate::components::Direction;
+
+/// Parses moves in LURD notation, ignoring case and whitespace. Returns the
+/// first character that isn't a move when there is one.
+pub fn parse_lurd(lurd: &str) -> Result<Vec<Direction>, char> {
+    lurd.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_ascii_lowercase() {
+            'l' => Ok(Direction::Left),
+            'u' => Ok(Direction::Up),
+            'r' => Ok(Direction::Right),
+            'd' => Ok(Direction::Down),
+            _ => Err(c),
+        })
+        .collect()
+}
@@ -0,0 +1,922 @@
+use crate::{
+    components::{
+        BoxColour, DeadSquares, Deadlocks, EventQueue, Gameplay, History, MapSize, Position, Rules,
+        Spawn,
+    },
+    deadlock::{find_dead_squares, DeadlockDetector},
+    entities::{create_box, create_box_spot, create_floor, create_player, create_wall},
+    solver::board_from_world,
+};
+use hecs::{Entity, World};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::fmt::Display;
+
+/// Colour given to every box and spot of a level loaded from the XSB format,
+/// which only knows a single kind of box.
+pub const XSB_BOX_COLOUR: BoxColour = BoxColour::Blue;
+
+/// The level played when no other level is given.
+const DEFAULT_MAP: &str = "
+    N N W W W W W W
+    W W W . . . . W
+    W . . . BB . . W
+    W . . RB . . . W 
+    W . P . . . . W
+    W . . . . RS . W
+    W . . BS . . . W
+    W . . . . . . W
+    W W W W W W W W
+    ";
+
+/// The notation a level map is written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapFormat {
+    /// The space separated token grid understood by `parse_map`
+    Tokens,
+    /// The standard XSB notation understood by `parse_xsb_map`
+    Xsb,
+}
+
+/// A level together with the metadata it was published with.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub id: String,
+    // Title of the collection the level comes from, ids only tell apart the
+    // levels of one collection
+    pub pack: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub copyright: Option<String>,
+    // Size declared by the level file, when it declares one
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub format: MapFormat,
+    pub map: String,
+    // How many boxes the player can push at once in this level
+    pub rules: Rules,
+}
+
+impl Level {
+    pub fn from_xsb(id: String, map: String) -> Self {
+        Self {
+            id,
+            pack: None,
+            title: None,
+            author: None,
+            copyright: None,
+            width: None,
+            height: None,
+            format: MapFormat::Xsb,
+            map,
+            rules: Rules::default(),
+        }
+    }
+
+    /// The name to show for this level, falling back to its id.
+    pub fn name(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Tells this level apart from the levels of other collections, which
+    /// can reuse the same ids.
+    pub fn key(&self) -> String {
+        let key = match &self.pack {
+            Some(pack) => format!("{}/{}", pack, self.id),
+            None => self.id.clone(),
+        };
+        // Kept on one line of the files it's written to
+        key.replace(['\t', '\n'], " ")
+    }
+}
+
+pub fn default_level() -> Level {
+    Level {
+        id: String::from("default"),
+        pack: None,
+        title: Some(String::from("Red and Blue")),
+        author: None,
+        copyright: None,
+        width: None,
+        height: None,
+        format: MapFormat::Tokens,
+        map: DEFAULT_MAP.to_string(),
+        rules: Rules::default(),
+    }
+}
+
+/// An ordered collection of levels, as loaded from a `.slc` file.
+#[derive(Clone, Debug, Default)]
+pub struct LevelPack {
+    pub title: Option<String>,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug)]
+pub enum LevelPackError {
+    Xml(roxmltree::Error),
+    NoLevels,
+}
+
+impl Display for LevelPackError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelPackError::Xml(error) => write!(fmt, "invalid level pack: {}", error),
+            LevelPackError::NoLevels => fmt.write_str("level pack contains no levels"),
+        }
+    }
+}
+
+/// Parses a SLC level collection:
+///
+/// ```xml
+/// <SokobanLevels>
+///   <Title>Pack title</Title>
+///   <LevelCollection Copyright="..." Author="...">
+///     <Level Id="1" Width="7" Height="3">
+///       <L>#######</L>
+///       <L>#@ $ .#</L>
+///       <L>#######</L>
+///     </Level>
+///   </LevelCollection>
+/// </SokobanLevels>
+/// ```
+///
+/// Levels are returned in the order they appear in the file. Author and
+/// copyright default to the ones of the enclosing collection.
+pub fn load_level_pack(xml: &str) -> Result<LevelPack, LevelPackError> {
+    let document = roxmltree::Document::parse(xml).map_err(LevelPackError::Xml)?;
+    let root = document.root_element();
+    let title = child_text(root, "Title").map(|title| title.trim().to_string());
+
+    let levels: Vec<Level> = root
+        .descendants()
+        .filter(|node| node.has_tag_name("Level"))
+        .enumerate()
+        .map(|(index, level)| {
+            let collection = level.parent_element();
+            let metadata = |name: &str| {
+                level
+                    .attribute(name)
+                    .or_else(|| child_text(level, name))
+                    .or_else(|| collection.and_then(|c| c.attribute(name)))
+                    .map(|value| value.trim().to_string())
+            };
+
+            let map = level
+                .children()
+                .filter(|node| node.has_tag_name("L"))
+                .map(|row| row.text().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            Level {
+                id: level
+                    .attribute("Id")
+                    .map(|id| id.trim().to_string())
+                    .unwrap_or_else(|| (index + 1).to_string()),
+                pack: title.clone(),
+                title: level
+                    .attribute("Title")
+                    .or_else(|| child_text(level, "Title"))
+                    .map(|title| title.trim().to_string()),
+                author: metadata("Author"),
+                copyright: metadata("Copyright"),
+                width: level.attribute("Width").and_then(|w| w.trim().parse().ok()),
+                height: level
+                    .attribute("Height")
+                    .and_then(|h| h.trim().parse().ok()),
+                format: MapFormat::Xsb,
+                map,
+                rules: metadata("MaxPushedBoxes")
+                    .and_then(|limit| parse_push_limit(&limit))
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    if levels.is_empty() {
+        return Err(LevelPackError::NoLevels);
+    }
+
+    Ok(LevelPack { title, levels })
+}
+
+/// Reads how many boxes can be pushed at once, a number of at least 1 or
+/// `any` for no limit.
+fn parse_push_limit(limit: &str) -> Option<Rules> {
+    let max_pushed_boxes = match limit {
+        "any" => None,
+        limit => Some(limit.parse().ok().filter(|max| *max >= 1)?),
+    };
+    Some(Rules { max_pushed_boxes })
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+}
+
+/// The largest number of rows or columns a map can have, limited by the
+/// coordinates `Position` and the size `MapSize` can hold.
+const MAX_MAP_SIZE: usize = u16::MAX as usize;
+
+/// Everything a single map cell can hold once parsed.
+#[derive(Clone, Copy, Default)]
+pub struct Tile {
+    pub floor: bool,
+    pub wall: bool,
+    pub player: bool,
+    pub box_colour: Option<BoxColour>,
+    pub spot_colour: Option<BoxColour>,
+}
+
+#[derive(Debug)]
+pub enum LevelErrorKind {
+    UnrecognizedItem(String),
+    RaggedRow {
+        expected: usize,
+        found: usize,
+    },
+    OutOfRange,
+    MissingPlayer,
+    ExtraPlayer,
+    NoBoxes,
+    BoxSpotMismatch {
+        colour: BoxColour,
+        boxes: usize,
+        spots: usize,
+    },
+    NotEnclosed,
+}
+
+/// A problem found while loading a level.
+#[derive(Debug)]
+pub struct LevelError {
+    // 1-based row and column of the problem, when it is tied to a cell
+    pub location: Option<(usize, usize)>,
+    pub kind: LevelErrorKind,
+}
+
+impl LevelError {
+    fn at(x: usize, y: usize, kind: LevelErrorKind) -> Self {
+        Self {
+            location: Some((y + 1, x + 1)),
+            kind,
+        }
+    }
+
+    fn anywhere(kind: LevelErrorKind) -> Self {
+        Self {
+            location: None,
+            kind,
+        }
+    }
+}
+
+impl Display for LevelError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((row, column)) = self.location {
+            write!(fmt, "row {}, column {}: ", row, column)?;
+        }
+
+        match &self.kind {
+            LevelErrorKind::UnrecognizedItem(item) => {
+                write!(fmt, "unrecognized map item {}", item)
+            }
+            LevelErrorKind::RaggedRow { expected, found } => write!(
+                fmt,
+                "row has {} columns but the first row has {}",
+                found, expected
+            ),
+            LevelErrorKind::OutOfRange => fmt.write_str("outside of the map bounds"),
+            LevelErrorKind::MissingPlayer => fmt.write_str("level has no player"),
+            LevelErrorKind::ExtraPlayer => fmt.write_str("level already has a player"),
+            LevelErrorKind::NoBoxes => fmt.write_str("level has no boxes"),
+            LevelErrorKind::BoxSpotMismatch {
+                colour,
+                boxes,
+                spots,
+            } => write!(
+                fmt,
+                "{} {} boxes but {} {} spots",
+                boxes, colour, spots, colour
+            ),
+            LevelErrorKind::NotEnclosed => fmt.write_str("floor is not enclosed by walls"),
+        }
+    }
+}
+
+/// Spawns the entities of a level in place of the current one. The level is
+/// only swapped in when it is valid, otherwise every problem found is returned
+/// and the world is left untouched.
+pub fn load_level(world: &mut World, level: &Level) -> Result<(), Vec<LevelError>> {
+    let (tiles, mut errors) = match level.format {
+        MapFormat::Tokens => parse_map(&level.map),
+        MapFormat::Xsb => parse_xsb_map(&level.map),
+    };
+
+    errors.extend(validate_level(&tiles, level.width, level.height));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    unload_level(world);
+    spawn_tiles(world, &tiles);
+
+    {
+        let mut query = world.query::<&mut MapSize>();
+        let map_size = query.iter().next().unwrap().1;
+        *map_size = MapSize {
+            width: tiles.iter().map(|row| row.len()).max().unwrap_or(0) as u16,
+            height: tiles.len() as u16,
+        };
+    }
+
+    // Where boxes get stuck only depends on the walls and spots, so it's
+    // worked out once per level
+    let (board, _) = board_from_world(world);
+    {
+        let mut query = world.query::<&mut DeadSquares>();
+        *query.iter().next().unwrap().1 = find_dead_squares(&board);
+    }
+    {
+        let mut query = world.query::<&mut Deadlocks>();
+        *query.iter().next().unwrap().1 = Deadlocks {
+            detector: DeadlockDetector::new(&board),
+            board,
+        };
+    }
+
+    let mut query = world.query::<&mut Rules>();
+    *query.iter().next().unwrap().1 = level.rules;
+
+    reset_level_progress(world);
+    Ok(())
+}
+
+/// Puts the current level back to its initial layout without reloading it.
+pub fn restart_level(world: &World) {
+    for (_, (position, spawn)) in world.query::<(&mut Position, &Spawn)>().iter() {
+        position.x = spawn.position.x;
+        position.y = spawn.position.y;
+    }
+
+    reset_level_progress(world);
+}
+
+/// Forgets everything that happened since the current level started.
+fn reset_level_progress(world: &World) {
+    let mut query = world.query::<&mut Gameplay>();
+    let gameplay = query.iter().next().unwrap().1;
+    *gameplay = Gameplay::default();
+
+    let mut query = world.query::<&mut EventQueue>();
+    let event_queue = query.iter().next().unwrap().1;
+    event_queue.events.clear();
+
+    let mut query = world.query::<&mut History>();
+    let history = query.iter().next().unwrap().1;
+    *history = History::default();
+}
+
+/// Despawns every entity that belongs to the current level, that is everything
+/// placed on the map, leaving the game wide entities like `Gameplay` alone.
+pub fn unload_level(world: &mut World) {
+    let level_entities: Vec<Entity> = world
+        .query::<&Position>()
+        .iter()
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in level_entities {
+        world.despawn(entity).unwrap();
+    }
+}
+
+fn spawn_tiles(world: &mut World, tiles: &[Vec<Tile>]) {
+    for (y, row) in tiles.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            // Create the position at which to create something on the map
+            let position = Position {
+                x: x as u16,
+                y: y as u16,
+                z: 0, // we will get the z from the factory functions
+            };
+
+            if tile.floor {
+                create_floor(world, position);
+            }
+            if tile.wall {
+                create_wall(world, position);
+            }
+            if let Some(colour) = tile.spot_colour {
+                create_box_spot(world, position, colour);
+            }
+            if let Some(colour) = tile.box_colour {
+                create_box(world, position, colour);
+            }
+            if tile.player {
+                create_player(world, position);
+            }
+        }
+    }
+}
+
+/// Parses the space separated token grid, e.g. `W . P BB RS N`. Unknown
+/// tokens are reported and read as floor, so the rest of the level can still
+/// be checked.
+pub fn parse_map(map_string: &str) -> (Vec<Vec<Tile>>, Vec<LevelError>) {
+    // read all lines
+    let rows: Vec<&str> = map_string.trim().split('\n').map(|x| x.trim()).collect();
+    let expected_columns = rows[0].split(' ').count();
+
+    let mut tiles = Vec::new();
+    let mut errors = Vec::new();
+
+    for (y, row) in rows.iter().enumerate() {
+        let columns: Vec<&str> = row.split(' ').collect();
+
+        if columns.len() != expected_columns {
+            errors.push(LevelError::at(
+                columns.len().min(expected_columns),
+                y,
+                LevelErrorKind::RaggedRow {
+                    expected: expected_columns,
+                    found: columns.len(),
+                },
+            ));
+        }
+
+        let mut tiles_row = Vec::new();
+        for (x, column) in columns.iter().enumerate() {
+            let floor = Tile {
+                floor: true,
+                ..Default::default()
+            };
+
+            // Figure out what object we should create
+            let tile = match *column {
+                "." => floor,
+                "W" => Tile {
+                    wall: true,
+                    ..floor
+                },
+                "P" => Tile {
+                    player: true,
+                    ..floor
+                },
+                "BB" => Tile {
+                    box_colour: Some(BoxColour::Blue),
+                    ..floor
+                },
+                "RB" => Tile {
+                    box_colour: Some(BoxColour::Red),
+                    ..floor
+                },
+                "BS" => Tile {
+                    spot_colour: Some(BoxColour::Blue),
+                    ..floor
+                },
+                "RS" => Tile {
+                    spot_colour: Some(BoxColour::Red),
+                    ..floor
+                },
+                "N" => Tile::default(),
+                c => {
+                    errors.push(LevelError::at(
+                        x,
+                        y,
+                        LevelErrorKind::UnrecognizedItem(c.to_string()),
+                    ));
+                    floor
+                }
+            };
+            tiles_row.push(tile);
+        }
+        tiles.push(tiles_row);
+    }
+
+    (tiles, errors)
+}
+
+/// Parses a level written in the standard XSB notation used by most public
+/// Sokoban collections:
+///
+/// ```text
+/// #  wall            $  box           .  spot
+/// @  player          *  box on spot   +  player on spot
+///    floor (also `-` and `_`)
+/// ```
+///
+/// Leading spaces are significant in XSB, so blank cells are only turned into
+/// floor when they are enclosed by the walls, everything outside is left empty.
+/// Unknown characters are reported and read as floor, like in `parse_map`.
+pub fn parse_xsb_map(map_string: &str) -> (Vec<Vec<Tile>>, Vec<LevelError>) {
+    // read all non blank lines, keeping the leading whitespace
+    let rows: Vec<Vec<char>> = map_string
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| x.trim_end().chars().collect())
+        .collect();
+
+    let interior = find_xsb_interior(&rows);
+
+    let mut tiles = Vec::new();
+    let mut errors = Vec::new();
+
+    for (y, row) in rows.iter().enumerate() {
+        let mut tiles_row = Vec::new();
+        for (x, column) in row.iter().enumerate() {
+            let floor = Tile {
+                floor: true,
+                ..Default::default()
+            };
+
+            // Figure out what object we should create
+            let tile = match *column {
+                ' ' | '-' | '_' if interior.contains(&(x, y)) => floor,
+                ' ' | '-' | '_' => Tile::default(),
+                '#' => Tile {
+                    wall: true,
+                    ..floor
+                },
+                '@' | 'p' => Tile {
+                    player: true,
+                    ..floor
+                },
+                '+' | 'P' => Tile {
+                    player: true,
+                    spot_colour: Some(XSB_BOX_COLOUR),
+                    ..floor
+                },
+                '$' | 'b' => Tile {
+                    box_colour: Some(XSB_BOX_COLOUR),
+                    ..floor
+                },
+                '*' | 'B' => Tile {
+                    box_colour: Some(XSB_BOX_COLOUR),
+                    spot_colour: Some(XSB_BOX_COLOUR),
+                    ..floor
+                },
+                '.' => Tile {
+                    spot_colour: Some(XSB_BOX_COLOUR),
+                    ..floor
+                },
+                c => {
+                    errors.push(LevelError::at(
+                        x,
+                        y,
+                        LevelErrorKind::UnrecognizedItem(c.to_string()),
+                    ));
+                    floor
+                }
+            };
+            tiles_row.push(tile);
+        }
+        tiles.push(tiles_row);
+    }
+
+    (tiles, errors)
+}
+
+/// Flood fills the level from every player, box and spot cell without
+/// crossing walls, returning the cells that lie inside the walls.
+fn find_xsb_interior(rows: &[Vec<char>]) -> HashSet<(usize, usize)> {
+    let mut interior = HashSet::new();
+    let mut to_visit: Vec<(usize, usize)> = Vec::new();
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, column) in row.iter().enumerate() {
+            if "@p+P$b*B.".contains(*column) {
+                to_visit.push((x, y));
+            }
+        }
+    }
+
+    while let Some((x, y)) = to_visit.pop() {
+        let column = rows.get(y).and_then(|row| row.get(x));
+        match column {
+            // walls and cells outside of the rows stop the fill
+            None | Some('#') => continue,
+            Some(_) => {
+                if !interior.insert((x, y)) {
+                    continue;
+                }
+            }
+        }
+
+        to_visit.push((x + 1, y));
+        to_visit.push((x, y + 1));
+        if x > 0 {
+            to_visit.push((x - 1, y));
+        }
+        if y > 0 {
+            to_visit.push((x, y - 1));
+        }
+    }
+
+    interior
+}
+
+/// Checks a parsed level for everything that would make it unplayable,
+/// returning all the problems found rather than stopping at the first one.
+/// `width` and `height` are the size the level file declared, if any.
+pub fn validate_level(
+    tiles: &[Vec<Tile>],
+    width: Option<usize>,
+    height: Option<usize>,
+) -> Vec<LevelError> {
+    let mut errors = Vec::new();
+
+    // Everything must fit both the declared size and what a position can hold
+    let max_width = width.unwrap_or(MAX_MAP_SIZE).min(MAX_MAP_SIZE);
+    let max_height = height.unwrap_or(MAX_MAP_SIZE).min(MAX_MAP_SIZE);
+    if tiles.len() > max_height {
+        errors.push(LevelError::at(0, max_height, LevelErrorKind::OutOfRange));
+    }
+    for (y, row) in tiles.iter().enumerate().take(max_height) {
+        if row.len() > max_width {
+            errors.push(LevelError::at(max_width, y, LevelErrorKind::OutOfRange));
+        }
+    }
+
+    let cells = || {
+        tiles
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, tile)| (x, y, tile)))
+    };
+
+    // Exactly one player
+    let players: Vec<(usize, usize)> = cells()
+        .filter(|(_, _, tile)| tile.player)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    if players.is_empty() {
+        errors.push(LevelError::anywhere(LevelErrorKind::MissingPlayer));
+    }
+    for (x, y) in players.iter().skip(1) {
+        errors.push(LevelError::at(*x, *y, LevelErrorKind::ExtraPlayer));
+    }
+
+    // As many boxes as spots for every colour
+    if cells().all(|(_, _, tile)| tile.box_colour.is_none()) {
+        errors.push(LevelError::anywhere(LevelErrorKind::NoBoxes));
+    }
+    for colour in [BoxColour::Red, BoxColour::Blue] {
+        let boxes = cells()
+            .filter(|(_, _, tile)| tile.box_colour == Some(colour))
+            .count();
+        let spots = cells()
+            .filter(|(_, _, tile)| tile.spot_colour == Some(colour))
+            .count();
+        if boxes != spots {
+            errors.push(LevelError::anywhere(LevelErrorKind::BoxSpotMismatch {
+                colour,
+                boxes,
+                spots,
+            }));
+        }
+    }
+
+    // Walls must enclose everything the player, boxes and spots can reach, so
+    // flood fill from them and report any floor that opens onto the outside.
+    let is_wall = |x: usize, y: usize| tiles[y][x].wall;
+    let is_inside = |x: usize, y: usize| {
+        tiles
+            .get(y)
+            .and_then(|row| row.get(x))
+            .is_some_and(|tile| tile.floor)
+    };
+
+    let mut visited = HashSet::new();
+    let mut leaks = BTreeSet::new();
+    let mut to_visit: Vec<(usize, usize)> = cells()
+        .filter(|(_, _, tile)| {
+            tile.player || tile.box_colour.is_some() || tile.spot_colour.is_some()
+        })
+        .map(|(x, y, _)| (x, y))
+        .collect();
+
+    while let Some((x, y)) = to_visit.pop() {
+        if is_wall(x, y) || !visited.insert((x, y)) {
+            continue;
+        }
+
+        let neighbours = [
+            x.checked_add(1).map(|x| (x, y)),
+            y.checked_add(1).map(|y| (x, y)),
+            x.checked_sub(1).map(|x| (x, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+        ];
+        for neighbour in neighbours {
+            match neighbour {
+                Some((x, y)) if is_inside(x, y) => to_visit.push((x, y)),
+                _ => {
+                    leaks.insert((y, x));
+                }
+            }
+        }
+    }
+
+    for (y, x) in leaks {
+        errors.push(LevelError::at(x, y, LevelErrorKind::NotEnclosed));
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_out_of_range(tiles: &[Vec<Tile>]) -> bool {
+        validate_level(tiles, None, None)
+            .iter()
+            .any(|error| matches!(error.kind, LevelErrorKind::OutOfRange))
+    }
+
+    #[test]
+    fn xsb_cells() {
+        let (tiles, errors) = parse_xsb_map("  ####\n###  #\n#+*$-#\n#    #\n######");
+        assert!(errors.is_empty());
+
+        // Blanks outside the walls are nothing, inside they are floor
+        let outside = tiles[0][0];
+        assert!(!outside.floor && !outside.wall);
+        assert!(tiles[0][2].wall);
+        let inside = tiles[1][3];
+        assert!(inside.floor && !inside.wall && inside.box_colour.is_none());
+        assert!(tiles[2][4].floor);
+
+        let player_on_spot = tiles[2][1];
+        assert!(player_on_spot.player);
+        assert_eq!(player_on_spot.spot_colour, Some(XSB_BOX_COLOUR));
+
+        let box_on_spot = tiles[2][2];
+        assert_eq!(box_on_spot.box_colour, Some(XSB_BOX_COLOUR));
+        assert_eq!(box_on_spot.spot_colour, Some(XSB_BOX_COLOUR));
+
+        let box_off_spot = tiles[2][3];
+        assert_eq!(box_off_spot.box_colour, Some(XSB_BOX_COLOUR));
+        assert_eq!(box_off_spot.spot_colour, None);
+    }
+
+    #[test]
+    fn slc_metadata() {
+        let pack = load_level_pack(
+            r#"
+            <SokobanLevels>
+              <Title> Pack </Title>
+              <LevelCollection Author="Collection author" Copyright="2001" MaxPushedBoxes="2">
+                <Level Id="first" Width="5" Height="3" Title="First level">
+                  <Author>Level author</Author>
+                  <L>#####</L>
+                  <L>#@$.#</L>
+                  <L>#####</L>
+                </Level>
+                <Level MaxPushedBoxes="any">
+                  <L>#####</L>
+                </Level>
+                <Level MaxPushedBoxes="0">
+                  <L>#####</L>
+                </Level>
+              </LevelCollection>
+            </SokobanLevels>
+            "#,
+        )
+        .unwrap();
+        assert_eq!(pack.title.as_deref(), Some("Pack"));
+
+        let first = &pack.levels[0];
+        assert_eq!(first.id, "first");
+        assert_eq!(first.pack.as_deref(), Some("Pack"));
+        assert_eq!(first.title.as_deref(), Some("First level"));
+        assert_eq!(first.author.as_deref(), Some("Level author"));
+        assert_eq!(first.copyright.as_deref(), Some("2001"));
+        assert_eq!((first.width, first.height), (Some(5), Some(3)));
+        assert_eq!(first.map, "#####\n#@$.#\n#####");
+        assert_eq!(first.rules.max_pushed_boxes, Some(2));
+
+        // Missing fields fall back to the collection's or to defaults
+        let second = &pack.levels[1];
+        assert_eq!(second.id, "2");
+        assert_eq!(second.title, None);
+        assert_eq!(second.author.as_deref(), Some("Collection author"));
+        assert_eq!((second.width, second.height), (None, None));
+        assert_eq!(second.rules.max_pushed_boxes, None);
+
+        // A limit that isn't a number of at least 1 is classic Sokoban
+        assert_eq!(pack.levels[2].rules.max_pushed_boxes, Some(1));
+    }
+
+    #[test]
+    fn slc_without_levels() {
+        let no_levels = load_level_pack("<SokobanLevels><Title>Empty</Title></SokobanLevels>");
+        assert!(matches!(no_levels, Err(LevelPackError::NoLevels)));
+        assert!(matches!(
+            load_level_pack("<SokobanLevels>"),
+            Err(LevelPackError::Xml(_))
+        ));
+    }
+
+    /// Every problem loading `map` reports, as shown to the player.
+    fn level_errors(format: MapFormat, map: &str, width: Option<usize>) -> Vec<String> {
+        let level = Level {
+            format,
+            width,
+            ..Level::from_xsb(String::from("test"), map.to_string())
+        };
+        load_level(&mut World::new(), &level)
+            .unwrap_err()
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn unknown_items_are_reported_with_the_rest() {
+        assert_eq!(
+            level_errors(MapFormat::Tokens, "W W W\nW X W\nW W W", None),
+            [
+                "row 2, column 2: unrecognized map item X",
+                "level has no player",
+                "level has no boxes",
+            ]
+        );
+        assert_eq!(
+            level_errors(MapFormat::Xsb, "#####\n#@$?#\n#####", None),
+            [
+                "row 2, column 4: unrecognized map item ?",
+                "1 blue boxes but 0 blue spots",
+            ]
+        );
+    }
+
+    #[test]
+    fn ragged_rows() {
+        assert_eq!(
+            level_errors(MapFormat::Tokens, "W W W W\nW P BB BS W\nW W W W", None),
+            ["row 2, column 5: row has 5 columns but the first row has 4"]
+        );
+    }
+
+    #[test]
+    fn rows_wider_than_declared() {
+        assert_eq!(
+            level_errors(MapFormat::Xsb, "#####\n#@$.#\n#####", Some(4)),
+            [
+                "row 1, column 5: outside of the map bounds",
+                "row 2, column 5: outside of the map bounds",
+                "row 3, column 5: outside of the map bounds",
+            ]
+        );
+    }
+
+    #[test]
+    fn player_count() {
+        assert_eq!(
+            level_errors(MapFormat::Xsb, "#####\n# $.#\n#####", None),
+            ["level has no player"]
+        );
+        assert_eq!(
+            level_errors(MapFormat::Xsb, "######\n#@$.@#\n######", None),
+            ["row 2, column 5: level already has a player"]
+        );
+    }
+
+    #[test]
+    fn boxes_and_spots() {
+        assert_eq!(
+            level_errors(MapFormat::Xsb, "####\n#@.#\n####", None),
+            ["level has no boxes", "0 blue boxes but 1 blue spots"]
+        );
+        assert_eq!(
+            level_errors(MapFormat::Tokens, "W W W W\nW P RB W\nW W W W", None),
+            ["1 red boxes but 0 red spots"]
+        );
+    }
+
+    #[test]
+    fn floor_open_to_the_outside() {
+        assert_eq!(
+            level_errors(MapFormat::Xsb, "#####\n#@$.\n#####", None),
+            ["row 2, column 4: floor is not enclosed by walls"]
+        );
+    }
+
+    #[test]
+    fn maps_fit_up_to_the_largest_map_size() {
+        let wide = |width| vec![vec![Tile::default(); width]];
+        let tall = |height| vec![vec![Tile::default()]; height];
+
+        assert!(!is_out_of_range(&wide(MAX_MAP_SIZE)));
+        assert!(!is_out_of_range(&tall(MAX_MAP_SIZE)));
+        assert!(is_out_of_range(&wide(MAX_MAP_SIZE + 1)));
+        assert!(is_out_of_range(&tall(MAX_MAP_SIZE + 1)));
+        // The width and height are kept as u16 in `MapSize`
+        assert!(u16::try_from(MAX_MAP_SIZE).is_ok());
+    }
+}
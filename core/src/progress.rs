@@ -0,0 +1,189 @@
+//! The levels a player solved and their best results.
+//!
+//! Progress is kept as text, one solved level per line with its fields
+//! separated by tabs, after a header giving the format version.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Version of the progress format written by `Progress::to_text`.
+pub const PROGRESS_VERSION: u32 = 1;
+
+const PROGRESS_HEADER: &str = "sokoban-rs progress";
+
+/// Best results on a solved level. Each one is the best seen so far, they
+/// don't have to come from the same solution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelRecord {
+    pub best_moves: u32,
+    pub best_pushes: u32,
+    pub best_time: Duration,
+    // Solution with the fewest moves, in LURD notation
+    pub best_solution: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Progress {
+    // Solved levels by `Level::key`
+    pub levels: BTreeMap<String, LevelRecord>,
+}
+
+#[derive(Debug)]
+pub enum ProgressError {
+    UnsupportedVersion(String),
+    // The line that couldn't be read
+    Malformed(String),
+}
+
+impl Display for ProgressError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgressError::UnsupportedVersion(header) => {
+                write!(fmt, "unsupported progress format \"{}\"", header)
+            }
+            ProgressError::Malformed(line) => {
+                write!(fmt, "can't read progress line \"{}\"", line)
+            }
+        }
+    }
+}
+
+impl Progress {
+    pub fn is_solved(&self, level_key: &str) -> bool {
+        self.levels.contains_key(level_key)
+    }
+
+    /// Marks a level as solved, keeping the best results.
+    pub fn record_win(
+        &mut self,
+        level_key: &str,
+        moves: u32,
+        pushes: u32,
+        time: Duration,
+        solution: &str,
+    ) {
+        let Some(record) = self.levels.get_mut(level_key) else {
+            self.levels.insert(
+                level_key.to_string(),
+                LevelRecord {
+                    best_moves: moves,
+                    best_pushes: pushes,
+                    best_time: time,
+                    best_solution: solution.to_string(),
+                },
+            );
+            return;
+        };
+
+        record.best_pushes = record.best_pushes.min(pushes);
+        record.best_time = record.best_time.min(time);
+        if moves < record.best_moves {
+            record.best_moves = moves;
+            record.best_solution = solution.to_string();
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", PROGRESS_HEADER, PROGRESS_VERSION);
+        for (level_key, record) in self.levels.iter() {
+            text.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                level_key,
+                record.best_moves,
+                record.best_pushes,
+                record.best_time.as_millis(),
+                record.best_solution,
+            ));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, ProgressError> {
+        let mut lines = text.lines();
+
+        let header = lines.next().unwrap_or_default();
+        if header != format!("{} {}", PROGRESS_HEADER, PROGRESS_VERSION) {
+            return Err(ProgressError::UnsupportedVersion(header.to_string()));
+        }
+
+        let mut progress = Progress::default();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let malformed = || ProgressError::Malformed(line.to_string());
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [level_key, moves, pushes, time, solution] = fields[..] else {
+                return Err(malformed());
+            };
+
+            progress.levels.insert(
+                level_key.to_string(),
+                LevelRecord {
+                    best_moves: moves.parse().map_err(|_| malformed())?,
+                    best_pushes: pushes.parse().map_err(|_| malformed())?,
+                    best_time: Duration::from_millis(time.parse().map_err(|_| malformed())?),
+                    best_solution: solution.to_string(),
+                },
+            );
+        }
+
+        Ok(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress() -> Progress {
+        let mut progress = Progress::default();
+        progress.record_win("Pack/1", 12, 3, Duration::from_millis(4_500), "rRuL");
+        progress.record_win("2", 7, 1, Duration::from_secs(61), "");
+        progress
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let progress = progress();
+        assert_eq!(Progress::from_text(&progress.to_text()).unwrap(), progress);
+        assert_eq!(
+            Progress::from_text(&Progress::default().to_text()).unwrap(),
+            Progress::default()
+        );
+    }
+
+    #[test]
+    fn unreadable_progress() {
+        let text = progress().to_text();
+        let broken = [
+            text.replace("progress 1", "progress 2"),
+            String::new(),
+            text.replace("\t4500\trRuL", ""),
+            text.replace("\t12\t", "\ttwelve\t"),
+            text.replace("\t61000\t", "\t-1\t"),
+        ];
+        for text in broken {
+            assert!(Progress::from_text(&text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn best_results_are_kept_separately() {
+        let mut progress = progress();
+        progress.record_win("Pack/1", 10, 5, Duration::from_secs(9), "rrRuL");
+        progress.record_win("Pack/1", 14, 2, Duration::from_secs(2), "rRuLrl");
+
+        assert_eq!(
+            progress.levels["Pack/1"],
+            LevelRecord {
+                best_moves: 10,
+                best_pushes: 2,
+                best_time: Duration::from_secs(2),
+                best_solution: String::from("rrRuL"),
+            }
+        );
+        assert!(progress.is_solved("2"));
+        assert!(!progress.is_solved("Pack/2"));
+    }
+}
@@ -0,0 +1,336 @@
+//! Saving a level in progress and picking it up again later.
+//!
+//! A save holds the steps taken on a level in LURD notation. Restoring takes
+//! them again on the freshly loaded level, which rebuilds the positions, the
+//! counters and the move history all at once. The positions of everything
+//! movable are saved too, to check the steps led back to the same place, and
+//! so is a hash of the map, to check the level wasn't changed in between.
+
+use crate::components::*;
+use crate::deadlock::is_position_lost;
+use crate::lurd::parse_lurd;
+use crate::map::Level;
+use crate::systems::gameplay::run_gameplay_state;
+use crate::systems::movement::take_grouped_step;
+use hecs::World;
+use std::fmt;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Version of the save format written by `SavedLevel::to_text`.
+pub const SAVE_VERSION: u32 = 2;
+
+const SAVE_HEADER: &str = "sokoban-rs save";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedLevel {
+    // The `Level::key` of the saved level
+    pub level_key: String,
+    pub map_hash: u64,
+    // Every step taken, minus the undone ones, in LURD notation
+    pub steps: String,
+    // Steps taken as part of a longer move along with the one before
+    pub grouped_steps: Vec<usize>,
+    pub elapsed: Duration,
+    // Where the player and the boxes were, sorted
+    pub movables: Vec<(u16, u16)>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    UnsupportedVersion(String),
+    // The line that couldn't be read
+    Malformed(String),
+    WrongLevel { expected: String, found: String },
+    // The level has the same key but a different map than when it was saved
+    MapChanged,
+    // A saved step that couldn't be taken again, 1-based
+    InvalidStep(usize),
+    PositionMismatch,
+}
+
+impl Display for SaveError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::UnsupportedVersion(header) => {
+                write!(fmt, "unsupported save format \"{}\"", header)
+            }
+            SaveError::Malformed(line) => write!(fmt, "can't read save line \"{}\"", line),
+            SaveError::WrongLevel { expected, found } => {
+                write!(fmt, "save is for level {}, not {}", found, expected)
+            }
+            SaveError::MapChanged => fmt.write_str("level map changed since it was saved"),
+            SaveError::InvalidStep(step) => write!(fmt, "saved step {} can't be taken", step),
+            SaveError::PositionMismatch => {
+                fmt.write_str("saved steps don't lead to the saved position")
+            }
+        }
+    }
+}
+
+impl SavedLevel {
+    /// Whether this save was made on `level`, as it is now.
+    pub fn is_for(&self, level: &Level) -> bool {
+        self.level_key == level.key() && self.map_hash == map_hash(&level.map)
+    }
+
+    pub fn to_text(&self) -> String {
+        let grouped_steps: Vec<String> = self
+            .grouped_steps
+            .iter()
+            .map(|step| step.to_string())
+            .collect();
+        let movables: Vec<String> = self
+            .movables
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, y))
+            .collect();
+
+        format!(
+            "{} {}\nlevel {}\nmap {:016x}\nsteps {}\ngrouped {}\nelapsed {}\nmovables {}\n",
+            SAVE_HEADER,
+            SAVE_VERSION,
+            self.level_key,
+            self.map_hash,
+            self.steps,
+            grouped_steps.join(" "),
+            self.elapsed.as_millis(),
+            movables.join(" "),
+        )
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, SaveError> {
+        let mut lines = text.lines();
+
+        let header = lines.next().unwrap_or_default();
+        if header != format!("{} {}", SAVE_HEADER, SAVE_VERSION) {
+            return Err(SaveError::UnsupportedVersion(header.to_string()));
+        }
+
+        let mut saved = SavedLevel {
+            level_key: String::new(),
+            map_hash: 0,
+            steps: String::new(),
+            grouped_steps: Vec::new(),
+            elapsed: Duration::ZERO,
+            movables: Vec::new(),
+        };
+
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let malformed = || SaveError::Malformed(line.to_string());
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+            match key {
+                "level" => saved.level_key = value.to_string(),
+                "map" => {
+                    saved.map_hash =
+                        u64::from_str_radix(value.trim(), 16).map_err(|_| malformed())?
+                }
+                "steps" => saved.steps = value.trim().to_string(),
+                "grouped" => {
+                    saved.grouped_steps = value
+                        .split_whitespace()
+                        .map(|step| step.parse().map_err(|_| malformed()))
+                        .collect::<Result<_, _>>()?
+                }
+                "elapsed" => {
+                    saved.elapsed =
+                        Duration::from_millis(value.trim().parse().map_err(|_| malformed())?)
+                }
+                "movables" => {
+                    saved.movables = value
+                        .split_whitespace()
+                        .map(|position| {
+                            let (x, y) = position.split_once(',').ok_or_else(malformed)?;
+                            Ok((
+                                x.parse().map_err(|_| malformed())?,
+                                y.parse().map_err(|_| malformed())?,
+                            ))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(saved)
+    }
+}
+
+/// Saves where `level`, loaded in `world`, is at.
+pub fn save_level(world: &World, level: &Level) -> SavedLevel {
+    let mut query = world.query::<&History>();
+    let history = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&Gameplay>();
+    let gameplay = query.iter().next().unwrap().1;
+
+    SavedLevel {
+        level_key: level.key(),
+        map_hash: map_hash(&level.map),
+        steps: history.to_lurd(),
+        grouped_steps: history
+            .undo
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| step.follows_previous)
+            .map(|(index, _)| index)
+            .collect(),
+        elapsed: gameplay.elapsed,
+        movables: movable_positions(world),
+    }
+}
+
+/// Takes the saved steps again on `level`, which must be the saved level as
+/// it was just loaded in `world`.
+pub fn restore_level(world: &World, level: &Level, saved: &SavedLevel) -> Result<(), SaveError> {
+    if saved.level_key != level.key() {
+        return Err(SaveError::WrongLevel {
+            expected: level.key(),
+            found: saved.level_key.clone(),
+        });
+    }
+    if !saved.is_for(level) {
+        return Err(SaveError::MapChanged);
+    }
+
+    let steps = parse_lurd(&saved.steps).map_err(|_| SaveError::Malformed(saved.steps.clone()))?;
+    for (index, direction) in steps.into_iter().enumerate() {
+        let follows_previous = saved.grouped_steps.contains(&index);
+        if !take_grouped_step(world, direction, follows_previous) {
+            return Err(SaveError::InvalidStep(index + 1));
+        }
+    }
+
+    if movable_positions(world) != saved.movables {
+        return Err(SaveError::PositionMismatch);
+    }
+
+    // The steps were taken in one go, nothing to react to
+    let position_lost = is_position_lost(world);
+    {
+        let mut query = world.query::<&mut EventQueue>();
+        query.iter().next().unwrap().1.events.clear();
+
+        let mut query = world.query::<&mut Gameplay>();
+        let gameplay = query.iter().next().unwrap().1;
+        gameplay.elapsed = saved.elapsed;
+        gameplay.position_lost = position_lost;
+    }
+    run_gameplay_state(world);
+
+    Ok(())
+}
+
+/// FNV-1a hash of a map, which unlike the standard library's hashers is
+/// the same from one build to the next.
+fn map_hash(map: &str) -> u64 {
+    map.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn movable_positions(world: &World) -> Vec<(u16, u16)> {
+    let mut positions: Vec<(u16, u16)> = world
+        .query::<(&Position, &Movable)>()
+        .iter()
+        .map(|(_, (position, _))| (position.x, position.y))
+        .collect();
+    positions.sort();
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{load_level, MapFormat};
+    use crate::solver::tests::load;
+    use crate::systems::movement::take_step;
+
+    const MAP: &str = "
+        ######
+        #@$ .#
+        ######
+    ";
+
+    fn saved() -> SavedLevel {
+        SavedLevel {
+            level_key: String::from("Pack/1"),
+            map_hash: map_hash("#####"),
+            steps: String::from("rRl"),
+            grouped_steps: vec![1, 2],
+            elapsed: Duration::from_millis(12_345),
+            movables: vec![(1, 1), (3, 1)],
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let saved = saved();
+        assert_eq!(SavedLevel::from_text(&saved.to_text()).unwrap(), saved);
+    }
+
+    #[test]
+    fn unreadable_saves() {
+        let text = saved().to_text();
+        let broken = [
+            text.replace("save 2", "save 1"),
+            String::new(),
+            text.replace("elapsed 12345", "elapsed"),
+            text.replace("elapsed 12345", "elapsed soon"),
+            text.replace("movables 1,1 3,1", "movables 1,1 3"),
+            text.replace("grouped 1 2", "grouped 1 two"),
+            text.replace("map ", "map z"),
+            text + "unknown line\n",
+        ];
+        for text in broken {
+            assert!(SavedLevel::from_text(&text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn saves_only_fit_their_level() {
+        let level = Level {
+            pack: Some(String::from("Pack")),
+            ..Level::from_xsb(String::from("1"), String::from("#####"))
+        };
+        assert!(saved().is_for(&level));
+
+        let changed_map = Level {
+            map: String::from("######"),
+            ..level.clone()
+        };
+        assert!(!saved().is_for(&changed_map));
+
+        let other_pack = Level {
+            pack: Some(String::from("Other pack")),
+            ..level
+        };
+        assert!(!saved().is_for(&other_pack));
+    }
+
+    #[test]
+    fn restoring_takes_the_saved_steps_again() {
+        let world = load(MAP, MapFormat::Xsb, Rules::default());
+        let rows: Vec<&str> = MAP.lines().map(str::trim).collect();
+        let level = Level::from_xsb(String::from("test"), rows.join("\n"));
+        assert!(take_step(&world, Direction::Right));
+        let saved = SavedLevel::from_text(&save_level(&world, &level).to_text()).unwrap();
+
+        let mut restored = load(MAP, MapFormat::Xsb, Rules::default());
+        restore_level(&restored, &level, &saved).unwrap();
+        assert_eq!(movable_positions(&restored), movable_positions(&world));
+        assert_eq!(save_level(&restored, &level), saved);
+
+        // A save that doesn't end where it was saved is refused
+        let moved = SavedLevel {
+            movables: vec![(1, 1), (2, 1)],
+            ..saved
+        };
+        load_level(&mut restored, &level).unwrap();
+        assert!(matches!(
+            restore_level(&restored, &level, &moved),
+            Err(SaveError::PositionMismatch)
+        ));
+    }
+}
@@ -0,0 +1,781 @@
+//! Searches for solutions to a level.
+//!
+//! The search works on pushes rather than single steps: two positions with
+//! the same boxes and the player anywhere in the same reachable region are
+//! the same state. States are explored best first, ordered by the pushes made
+//! so far plus a weighted lower bound of the pushes left. The weight trades
+//! the optimal number of pushes for a much faster search, solutions stay close
+//! to the best one. The walking between pushes is filled in afterwards.
+
+use crate::components::*;
+use crate::deadlock::DeadlockDetector;
+use hecs::World;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+// How much more the pushes left count than the pushes made, 1 would only
+// ever find solutions with the fewest pushes
+const ESTIMATE_WEIGHT: usize = 3;
+
+/// The parts of a level that never move: its size, walls and spots.
+#[derive(Clone, Default)]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    walls: Vec<bool>,
+    spots: Vec<Option<BoxColour>>,
+}
+
+impl Board {
+    pub fn cell(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width + x as usize
+    }
+
+    pub fn coordinates(&self, cell: usize) -> (u16, u16) {
+        ((cell % self.width) as u16, (cell / self.width) as u16)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_wall(&self, cell: usize) -> bool {
+        self.walls[cell]
+    }
+
+    pub fn spot(&self, cell: usize) -> Option<BoxColour> {
+        self.spots[cell]
+    }
+
+    /// The cell next to `cell` in `direction`, if it is on the board.
+    pub fn neighbour(&self, cell: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = (cell % self.width, cell / self.width);
+        match direction {
+            Direction::Up if y > 0 => Some(cell - self.width),
+            Direction::Down if y + 1 < self.height => Some(cell + self.width),
+            Direction::Left if x > 0 => Some(cell - 1),
+            Direction::Right if x + 1 < self.width => Some(cell + 1),
+            _ => None,
+        }
+    }
+
+    /// The cell next to `cell` in `direction`, if it is on the board and
+    /// not a wall.
+    pub fn open_neighbour(&self, cell: usize, direction: Direction) -> Option<usize> {
+        self.neighbour(cell, direction)
+            .filter(|neighbour| !self.is_wall(*neighbour))
+    }
+}
+
+/// Where the boxes and the player are at one point of the game.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct BoardState {
+    pub player: usize,
+    // Sorted by cell so equal positions compare equal
+    pub boxes: Vec<(usize, BoxColour)>,
+}
+
+impl BoardState {
+    pub fn new(player: usize, mut boxes: Vec<(usize, BoxColour)>) -> Self {
+        boxes.sort_by_key(|(cell, _)| *cell);
+        Self { player, boxes }
+    }
+
+    pub fn box_at(&self, cell: usize) -> Option<BoxColour> {
+        self.boxes
+            .binary_search_by_key(&cell, |(cell, _)| *cell)
+            .ok()
+            .map(|index| self.boxes[index].1)
+    }
+
+    pub fn is_solved(&self, board: &Board) -> bool {
+        self.boxes
+            .iter()
+            .all(|(cell, colour)| board.spot(*cell) == Some(*colour))
+    }
+
+    /// The cells the player can walk to without pushing anything.
+    pub fn reachable(&self, board: &Board) -> Vec<bool> {
+        let mut reachable = vec![false; board.cell_count()];
+        let mut to_visit = vec![self.player];
+        reachable[self.player] = true;
+
+        while let Some(cell) = to_visit.pop() {
+            for direction in DIRECTIONS {
+                if let Some(next) = board.open_neighbour(cell, direction) {
+                    if !reachable[next] && self.box_at(next).is_none() {
+                        reachable[next] = true;
+                        to_visit.push(next);
+                    }
+                }
+            }
+        }
+
+        reachable
+    }
+
+    /// How many boxes in a row would move when pushing the box at `cell`
+    /// towards `direction`, if the board and the rules allow the push.
+    pub fn push_length(
+        &self,
+        board: &Board,
+        rules: &Rules,
+        cell: usize,
+        direction: Direction,
+    ) -> Option<usize> {
+        let mut pushed = 0;
+        let mut next = Some(cell);
+
+        while let Some(current) = next {
+            if board.is_wall(current) {
+                return None;
+            }
+            if self.box_at(current).is_none() {
+                return rules.can_push(pushed).then_some(pushed);
+            }
+            pushed += 1;
+            next = board.neighbour(current, direction);
+        }
+
+        None
+    }
+
+    /// Pushes the `pushed` boxes starting at `cell` one tile in `direction`,
+    /// moving the player into `cell`.
+    pub fn push(&self, board: &Board, cell: usize, direction: Direction, pushed: usize) -> Self {
+        let mut train = Vec::with_capacity(pushed);
+        let mut current = cell;
+        for _ in 0..pushed {
+            train.push(current);
+            current = board.neighbour(current, direction).unwrap();
+        }
+
+        let boxes = self
+            .boxes
+            .iter()
+            .map(|(box_cell, colour)| {
+                if train.contains(box_cell) {
+                    (board.neighbour(*box_cell, direction).unwrap(), *colour)
+                } else {
+                    (*box_cell, *colour)
+                }
+            })
+            .collect();
+
+        Self::new(cell, boxes)
+    }
+
+    /// The same state with the player moved to the first cell of its
+    /// reachable region, so positions that only differ by walking compare
+    /// equal.
+    fn normalized(&self, board: &Board) -> Self {
+        let player = self
+            .reachable(board)
+            .iter()
+            .position(|reachable| *reachable)
+            .unwrap();
+
+        Self {
+            player,
+            boxes: self.boxes.clone(),
+        }
+    }
+}
+
+/// Reads the board and the current position of the boxes and player from
+/// a world with a level loaded.
+pub fn board_from_world(world: &World) -> (Board, BoardState) {
+    let mut query = world.query::<&MapSize>();
+    let map_size = *query.iter().next().unwrap().1;
+
+    let mut board = Board {
+        width: map_size.width as usize,
+        height: map_size.height as usize,
+        walls: vec![false; map_size.width as usize * map_size.height as usize],
+        spots: vec![None; map_size.width as usize * map_size.height as usize],
+    };
+
+    for (_, (position, _)) in world.query::<(&Position, &Wall)>().iter() {
+        let cell = board.cell(position.x, position.y);
+        board.walls[cell] = true;
+    }
+    for (_, (position, spot)) in world.query::<(&Position, &BoxSpot)>().iter() {
+        let cell = board.cell(position.x, position.y);
+        board.spots[cell] = Some(spot.colour);
+    }
+
+    let state = state_from_world(world, &board);
+    (board, state)
+}
+
+/// Reads the current position of the boxes and player on `board` from a
+/// world with its level loaded.
+pub fn state_from_world(world: &World, board: &Board) -> BoardState {
+    let boxes = world
+        .query::<(&Position, &Box)>()
+        .iter()
+        .map(|(_, (position, the_box))| (board.cell(position.x, position.y), the_box.colour))
+        .collect();
+    let player = world
+        .query::<(&Position, &Player)>()
+        .iter()
+        .map(|(_, (position, _))| board.cell(position.x, position.y))
+        .next()
+        .unwrap();
+
+    BoardState::new(player, boxes)
+}
+
+/// A push of the box at `x`, `y` one tile towards `direction`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Push {
+    pub x: u16,
+    pub y: u16,
+    pub direction: Direction,
+}
+
+#[derive(Clone, Debug)]
+pub struct Solution {
+    pub pushes: Vec<Push>,
+    // Every step, walks included, in LURD notation
+    pub lurd: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum SolverStatus {
+    // Still searching, more nodes need to be expanded
+    Searching,
+    Solved(Solution),
+    Unsolvable,
+}
+
+/// How much searching `Solver::solve` may do before giving up.
+#[derive(Clone, Copy, Default)]
+pub struct SolverBudget {
+    pub max_nodes: Option<usize>,
+    // Not available on wasm32, where there is no clock to check it against
+    pub max_time: Option<Duration>,
+}
+
+struct Node {
+    state: BoardState,
+    parent: Option<usize>,
+    push: Option<Push>,
+    pushes: usize,
+}
+
+pub struct Solver {
+    board: Board,
+    rules: Rules,
+    initial: BoardState,
+    deadlocks: DeadlockDetector,
+    // Whether to look for corral deadlocks, which takes a search of its own
+    prune_corrals: bool,
+    nodes: Vec<Node>,
+    seen: HashMap<BoardState, usize>,
+    // Nodes left to expand, by lowest estimated total pushes then most pushes
+    open: BinaryHeap<Reverse<(usize, Reverse<usize>, usize)>>,
+    // Nodes expanded so far, what the node budget counts
+    expanded: usize,
+    status: SolverStatus,
+}
+
+impl Solver {
+    pub fn new(board: Board, initial: BoardState, rules: Rules) -> Self {
+        let deadlocks = DeadlockDetector::new(&board);
+        Self::with_deadlocks(board, initial, rules, deadlocks, true)
+    }
+
+    pub(crate) fn with_deadlocks(
+        board: Board,
+        initial: BoardState,
+        rules: Rules,
+        deadlocks: DeadlockDetector,
+        prune_corrals: bool,
+    ) -> Self {
+        let mut solver = Self {
+            board,
+            rules,
+            initial: initial.clone(),
+            deadlocks,
+            prune_corrals,
+            nodes: Vec::new(),
+            seen: HashMap::new(),
+            open: BinaryHeap::new(),
+            expanded: 0,
+            status: SolverStatus::Searching,
+        };
+
+        match solver.estimate(&initial) {
+            Some(estimate) => solver.add_node(initial, None, None, 0, estimate),
+            None => solver.status = SolverStatus::Unsolvable,
+        }
+
+        solver
+    }
+
+    /// A solver for the level loaded in `world`, from its current position.
+    /// The board and deadlock detector come from the ones built when the
+    /// level was loaded.
+    pub fn from_world(world: &World) -> Self {
+        let mut query = world.query::<&Deadlocks>();
+        let deadlocks = query.iter().next().unwrap().1;
+        let initial = state_from_world(world, &deadlocks.board);
+
+        let mut query = world.query::<&Rules>();
+        let rules = *query.iter().next().unwrap().1;
+
+        Self::with_deadlocks(
+            deadlocks.board.clone(),
+            initial,
+            rules,
+            deadlocks.detector.clone(),
+            true,
+        )
+    }
+
+    pub fn status(&self) -> &SolverStatus {
+        &self.status
+    }
+
+    /// How many positions have been found so far.
+    pub fn explored_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Searches until the level is solved, shown to be unsolvable or the
+    /// budget runs out, in which case the status is still `Searching` and
+    /// solving can carry on with another call.
+    pub fn solve(&mut self, budget: SolverBudget) -> SolverStatus {
+        #[cfg(not(target_arch = "wasm32"))]
+        let started = Instant::now();
+        let expanded_before = self.expanded;
+
+        while matches!(self.status, SolverStatus::Searching) {
+            let expanded = self.expanded - expanded_before;
+            if budget
+                .max_nodes
+                .is_some_and(|max_nodes| expanded >= max_nodes)
+            {
+                break;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if budget
+                .max_time
+                .is_some_and(|max_time| started.elapsed() >= max_time)
+            {
+                break;
+            }
+
+            // Check the clock every few nodes rather than every single one
+            let nodes = budget
+                .max_nodes
+                .map_or(64, |max_nodes| 64.min(max_nodes - expanded));
+            self.step(nodes);
+        }
+
+        self.status.clone()
+    }
+
+    /// Expands up to `nodes` positions, so a search can be spread over
+    /// several frames.
+    pub fn step(&mut self, nodes: usize) -> SolverStatus {
+        for _ in 0..nodes {
+            if !matches!(self.status, SolverStatus::Searching) {
+                break;
+            }
+
+            let Some(Reverse((_, _, index))) = self.open.pop() else {
+                self.status = SolverStatus::Unsolvable;
+                break;
+            };
+
+            if self.nodes[index].state.is_solved(&self.board) {
+                self.status = SolverStatus::Solved(self.solution(index));
+                break;
+            }
+
+            self.expand(index);
+            self.expanded += 1;
+        }
+
+        self.status.clone()
+    }
+
+    fn expand(&mut self, index: usize) {
+        let state = self.nodes[index].state.clone();
+        let pushes = self.nodes[index].pushes + 1;
+        let reachable = state.reachable(&self.board);
+
+        for (cell, _) in state.boxes.iter() {
+            for direction in DIRECTIONS {
+                // The player has to stand on the other side of the box
+                let Some(from) = self.board.neighbour(*cell, opposite(direction)) else {
+                    continue;
+                };
+                if !reachable[from] {
+                    continue;
+                }
+                let Some(pushed) = state.push_length(&self.board, &self.rules, *cell, direction)
+                else {
+                    continue;
+                };
+
+                let next = state.push(&self.board, *cell, direction, pushed);
+                let Some(estimate) = self.estimate(&next) else {
+                    continue;
+                };
+
+                // Skip positions that can't lead to a solution anymore
+                let mut moved = Vec::with_capacity(pushed);
+                let mut moved_to = self.board.neighbour(*cell, direction).unwrap();
+                for _ in 0..pushed {
+                    moved.push(moved_to);
+                    moved_to = self
+                        .board
+                        .neighbour(moved_to, direction)
+                        .unwrap_or(moved_to);
+                }
+                if self
+                    .deadlocks
+                    .is_freeze_deadlock(&self.board, &self.rules, &next, &moved)
+                {
+                    continue;
+                }
+                if self.prune_corrals
+                    && self
+                        .deadlocks
+                        .is_corral_deadlock(&self.board, &self.rules, &next)
+                {
+                    continue;
+                }
+
+                let (x, y) = self.board.coordinates(*cell);
+                let push = Push { x, y, direction };
+                self.add_node(next, Some(index), Some(push), pushes, estimate);
+            }
+        }
+    }
+
+    fn add_node(
+        &mut self,
+        state: BoardState,
+        parent: Option<usize>,
+        push: Option<Push>,
+        pushes: usize,
+        estimate: usize,
+    ) {
+        let key = state.normalized(&self.board);
+        if self.seen.contains_key(&key) {
+            return;
+        }
+
+        let index = self.nodes.len();
+        self.seen.insert(key, index);
+        self.nodes.push(Node {
+            state,
+            parent,
+            push,
+            pushes,
+        });
+        self.open.push(Reverse((
+            pushes + ESTIMATE_WEIGHT * estimate,
+            Reverse(pushes),
+            index,
+        )));
+    }
+
+    /// A lower bound of the pushes left, or `None` when a box can't reach
+    /// any spot of its colour anymore.
+    fn estimate(&self, state: &BoardState) -> Option<usize> {
+        let mut estimate = 0;
+        for (cell, colour) in state.boxes.iter() {
+            if self.deadlocks.is_dead_square(*cell, *colour) {
+                return None;
+            }
+            estimate += self.deadlocks.push_distance(*cell, *colour);
+        }
+        Some(estimate)
+    }
+
+    /// Walks back from a solved node to the start, then replays the pushes
+    /// to fill in the walking in between.
+    fn solution(&self, index: usize) -> Solution {
+        let mut pushes = Vec::new();
+        let mut current = Some(index);
+        while let Some(index) = current {
+            if let Some(push) = self.nodes[index].push {
+                pushes.push(push);
+            }
+            current = self.nodes[index].parent;
+        }
+        pushes.reverse();
+
+        let mut lurd = String::new();
+        let mut state = self.initial.clone();
+        for push in pushes.iter() {
+            let cell = self.board.cell(push.x, push.y);
+            let from = self
+                .board
+                .neighbour(cell, opposite(push.direction))
+                .unwrap();
+
+            for direction in walk_path(&self.board, &state, from).unwrap() {
+                lurd.push(direction.to_lurd(false));
+            }
+            lurd.push(push.direction.to_lurd(true));
+
+            let pushed = state
+                .push_length(&self.board, &self.rules, cell, push.direction)
+                .unwrap();
+            state = state.push(&self.board, cell, push.direction, pushed);
+        }
+
+        Solution { pushes, lurd }
+    }
+}
+
+pub fn opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Down,
+        Direction::Down => Direction::Up,
+        Direction::Left => Direction::Right,
+        Direction::Right => Direction::Left,
+    }
+}
+
+/// The shortest walk from the player to `to` that doesn't push anything, if
+/// there is one.
+pub fn walk_path(board: &Board, state: &BoardState, to: usize) -> Option<Vec<Direction>> {
+    let mut came_from: HashMap<usize, (usize, Direction)> = HashMap::new();
+    let mut to_visit = VecDeque::from([state.player]);
+
+    while let Some(cell) = to_visit.pop_front() {
+        if cell == to {
+            let mut path = Vec::new();
+            let mut current = cell;
+            while let Some((previous, direction)) = came_from.get(&current) {
+                path.push(*direction);
+                current = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for direction in DIRECTIONS {
+            if let Some(next) = board.open_neighbour(cell, direction) {
+                if next != state.player
+                    && !came_from.contains_key(&next)
+                    && state.box_at(next).is_none()
+                {
+                    came_from.insert(next, (cell, direction));
+                    to_visit.push_back(next);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::entities::*;
+    use crate::lurd::parse_lurd;
+    use crate::map::{load_level, Level, MapFormat};
+    use crate::systems::movement::take_step;
+
+    /// A world with `map` loaded as a level, in XSB or, for coloured boxes,
+    /// in the token format. Rows are trimmed so maps can be indented, each
+    /// one has to start and end with a wall.
+    pub(crate) fn load(map: &str, format: MapFormat, rules: Rules) -> World {
+        let mut world = World::new();
+        create_gameplay(&mut world);
+        create_map_size(&mut world);
+        create_history(&mut world);
+        create_rules(&mut world);
+        create_event_queue(&mut world);
+        create_dead_squares(&mut world);
+        create_deadlocks(&mut world);
+
+        let rows: Vec<&str> = map.lines().map(str::trim).collect();
+        let level = Level {
+            format,
+            rules,
+            ..Level::from_xsb(String::from("test"), rows.join("\n"))
+        };
+        load_level(&mut world, &level).unwrap();
+        world
+    }
+
+    fn solve(world: &World, budget: SolverBudget) -> (Solver, SolverStatus) {
+        let mut solver = Solver::from_world(world);
+        let status = solver.solve(budget);
+        (solver, status)
+    }
+
+    /// Takes the steps of `lurd` in `world`, returning whether every step
+    /// could be taken and left the level solved.
+    fn solves(world: &World, lurd: &str) -> bool {
+        let steps_taken = parse_lurd(lurd)
+            .unwrap()
+            .into_iter()
+            .all(|direction| take_step(world, direction));
+        let (board, state) = board_from_world(world);
+        steps_taken && state.is_solved(&board)
+    }
+
+    #[test]
+    fn solution_solves_the_level() {
+        let world = load(
+            "
+                ######
+                #    #
+                # $$ #
+                # .. #
+                #@   #
+                ######
+            ",
+            MapFormat::Xsb,
+            Rules::default(),
+        );
+
+        let SolverStatus::Solved(solution) = solve(&world, SolverBudget::default()).1 else {
+            panic!("level not solved");
+        };
+        assert_eq!(solution.pushes.len(), 2);
+        assert!(solves(&world, &solution.lurd), "{}", solution.lurd);
+    }
+
+    #[test]
+    fn level_without_pushes_is_unsolvable() {
+        // The boxes block each other, none of them can be pushed
+        let world = load(
+            "
+                #######
+                #     #
+                # $$  #
+                # $$  #
+                #@ .. #
+                #  .. #
+                #######
+            ",
+            MapFormat::Xsb,
+            Rules::default(),
+        );
+
+        let status = solve(&world, SolverBudget::default()).1;
+        assert!(matches!(status, SolverStatus::Unsolvable));
+    }
+
+    #[test]
+    fn boxes_only_count_on_spots_of_their_colour() {
+        // Pushing both boxes straight down puts each on the other's spot
+        let world = load(
+            "
+                W W W W W W W W
+                W . . . . . . W
+                W . . RB BB . . W
+                W . . BS RS . . W
+                W . . . . . . W
+                W P . . . . . W
+                W W W W W W W W
+            ",
+            MapFormat::Tokens,
+            Rules::default(),
+        );
+        let (board, state) = board_from_world(&world);
+
+        let pushed_down = BoardState::new(
+            state.player,
+            state
+                .boxes
+                .iter()
+                .map(|(cell, colour)| (board.neighbour(*cell, Direction::Down).unwrap(), *colour))
+                .collect(),
+        );
+        assert!(!pushed_down.is_solved(&board));
+
+        let SolverStatus::Solved(solution) = solve(&world, SolverBudget::default()).1 else {
+            panic!("level not solved");
+        };
+        assert!(solves(&world, &solution.lurd), "{}", solution.lurd);
+    }
+
+    #[test]
+    fn search_stops_when_the_budget_runs_out() {
+        let world = load(
+            "
+                ######
+                #    #
+                # $$ #
+                # .. #
+                #@   #
+                ######
+            ",
+            MapFormat::Xsb,
+            Rules::default(),
+        );
+        let no_nodes = SolverBudget {
+            max_nodes: Some(0),
+            max_time: None,
+        };
+        let no_time = SolverBudget {
+            max_nodes: None,
+            max_time: Some(Duration::ZERO),
+        };
+
+        for budget in [no_nodes, no_time] {
+            let (mut solver, status) = solve(&world, budget);
+            assert!(matches!(status, SolverStatus::Searching));
+            assert_eq!(solver.explored_nodes(), 1);
+
+            // The search carries on where it stopped
+            let status = solver.solve(SolverBudget::default());
+            assert!(matches!(status, SolverStatus::Solved(_)));
+        }
+    }
+
+    #[test]
+    fn node_budget_is_respected_exactly() {
+        let world = load(
+            "
+                ########
+                #      #
+                # $$$  #
+                #      #
+                # ...  #
+                #@     #
+                ########
+            ",
+            MapFormat::Xsb,
+            Rules::default(),
+        );
+
+        for max_nodes in [1, 3] {
+            let budget = SolverBudget {
+                max_nodes: Some(max_nodes),
+                max_time: None,
+            };
+            let (mut solver, status) = solve(&world, budget);
+            assert!(matches!(status, SolverStatus::Searching));
+            assert_eq!(solver.expanded, max_nodes);
+
+            // Another call gets a budget of its own
+            solver.solve(budget);
+            assert_eq!(solver.expanded, 2 * max_nodes);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::components::*;
+use crate::deadlock::is_position_lost;
+use crate::events::*;
+use hecs::World;
+
+use std::collections::HashMap;
+
+/// Takes the events fired since the last call out of the queue, queueing the
+/// events they lead to, and returns them so a frontend can react to them.
+pub fn run_process_events(world: &mut World) -> Vec<Event> {
+    let events = {
+        let mut query = world.query::<&mut crate::components::EventQueue>();
+        let events = query
+            .iter()
+            .next()
+            .unwrap()
+            .1
+            .events
+            .drain(..)
+            .collect::<Vec<_>>();
+
+        events
+    };
+
+    let mut new_events = Vec::new();
+    let mut boxes_moved = false;
+
+    let mut query = world.query::<(&Position, &BoxSpot)>();
+    let box_spots_by_position: HashMap<(u16, u16), &BoxSpot> = query
+        .iter()
+        .map(|(_, t)| ((t.0.x, t.0.y), t.1))
+        .collect::<HashMap<_, _>>();
+
+    let mut query = world.query::<&DeadSquares>();
+    let dead_squares = query.iter().next().unwrap().1;
+
+    for event in events.iter() {
+        if let Event::EntityMoved(EntityMoved { entity }) = event {
+            // An entity was just moved, check if it was a box and fire
+            // more events if it's been moved on a spot.
+            if let Ok(the_box) = world.get::<&Box>(*entity) {
+                boxes_moved = true;
+
+                if let Ok(box_position) = world.get::<&Position>(*entity) {
+                    // Check if there is a spot on this position, and if there
+                    // is if it's the correct or incorrect type
+                    if let Some(box_spot) =
+                        box_spots_by_position.get(&(box_position.x, box_position.y))
+                    {
+                        new_events.push(Event::BoxPlacedOnSpot(BoxPlacedOnSpot {
+                            is_correct_spot: (box_spot.colour == the_box.colour),
+                        }));
+                    }
+
+                    if dead_squares.contains(&box_position, the_box.colour) {
+                        new_events.push(Event::BoxStuck(BoxStuck { entity: *entity }));
+                    }
+                }
+            }
+        }
+    }
+
+    // Only moving boxes can get the level into or out of a deadlock
+    if boxes_moved {
+        let position_lost = is_position_lost(world);
+
+        let mut query = world.query::<&mut Gameplay>();
+        let gameplay = query.iter().next().unwrap().1;
+        gameplay.position_lost = position_lost;
+    }
+
+    // Finally add events back into the world
+    {
+        let mut query = world.query::<&mut EventQueue>();
+        let event_queue = query.iter().next().unwrap().1;
+        event_queue.events.append(&mut new_events);
+    }
+
+    events
+}
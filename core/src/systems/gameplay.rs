@@ -0,0 +1,60 @@
+use crate::components::*;
+use hecs::World;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub fn run_gameplay_state(world: &World) {
+    // get all boxes indexed by position
+    let mut query = world.query::<(&Position, &Box)>();
+    let boxes_by_position: HashMap<(u16, u16), &Box> = query
+        .iter()
+        .map(|(_, t)| ((t.0.x, t.0.y), t.1))
+        .collect::<HashMap<_, _>>();
+
+    // loop through all box spots and check if there is a corresponding
+    // box at that position
+    let boxes_total = world.query::<&BoxSpot>().iter().count();
+    let boxes_out_of_position: usize = world
+        .query::<(&Position, &BoxSpot)>()
+        .iter()
+        .map(|(_, (position, box_spot))| {
+            if let Some(the_box) = boxes_by_position.get(&(position.x, position.y)) {
+                if box_spot.colour == the_box.colour {
+                    0
+                } else {
+                    1
+                }
+            } else {
+                1
+            }
+        })
+        .collect::<Vec<usize>>()
+        .into_iter()
+        .sum();
+
+    // If we made it this far, then all box spots have boxes on them, and the
+    // game has been won. Undoing a move can take a won level back to playing.
+    let mut query = world.query::<&mut Gameplay>();
+    let gameplay = query.iter().next().unwrap().1;
+    gameplay.boxes_total = boxes_total;
+    gameplay.boxes_on_spot = boxes_total - boxes_out_of_position;
+    gameplay.state = if boxes_out_of_position == 0 {
+        GameplayState::Won
+    } else if gameplay.position_lost {
+        GameplayState::Lost
+    } else {
+        GameplayState::Playing
+    };
+}
+
+/// Counts the time spent playing, the clock only runs between the first move
+/// and winning the level. A lost position can still be undone, so the clock
+/// keeps running.
+pub fn run_gameplay_timer(world: &World, frame_time: Duration) {
+    let mut query = world.query::<&mut Gameplay>();
+    let gameplay = query.iter().next().unwrap().1;
+
+    if !matches!(gameplay.state, GameplayState::Won) && gameplay.moves_count > 0 {
+        gameplay.elapsed += frame_time;
+    }
+}
@@ -0,0 +1,3 @@
+pub mod events;
+pub mod gameplay;
+pub mod movement;
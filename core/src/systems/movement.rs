@@ -0,0 +1,365 @@
+use crate::components::*;
+use crate::events::*;
+use crate::solver::DIRECTIONS;
+use hecs::{Entity, World};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Moves the player and records the step so it can be undone. Returns whether
+/// the player could move.
+pub fn take_step(world: &World, direction: Direction) -> bool {
+    take_grouped_step(world, direction, false)
+}
+
+/// Like `take_step`, but when `follows_previous` is set the step is undone
+/// and redone together with the one before it, so a longer move made of
+/// several steps can be taken back as a whole.
+pub fn take_grouped_step(world: &World, direction: Direction, follows_previous: bool) -> bool {
+    let Some(mut step) = move_player(world, direction) else {
+        return false;
+    };
+    step.follows_previous = follows_previous;
+
+    // A new move replaces whatever could have been redone
+    let mut query = world.query::<&mut History>();
+    let history = query.iter().next().unwrap().1;
+    history.undo.push(step);
+    history.redo.clear();
+    true
+}
+
+/// Moves the player one tile in `direction`, pushing anything movable in the
+/// way. Returns the step taken, or `None` when the player couldn't move.
+pub fn move_player(world: &World, direction: Direction) -> Option<Step> {
+    let to_move = entities_to_move(world, direction);
+    let mut events = Vec::new();
+    if to_move.is_empty() {
+        events.push(Event::PlayerHitObstacle {});
+    }
+
+    // Update gameplay moves, anything moving along with the player
+    // means a box was pushed
+    let pushed = to_move.len() > 1;
+    if !to_move.is_empty() {
+        let mut query = world.query::<&mut Gameplay>();
+        let gameplay = query.iter().next().unwrap().1;
+        gameplay.moves_count += 1;
+        if pushed {
+            gameplay.pushes_count += 1;
+        }
+    }
+
+    // Now actually move what needs to be moved, remembering where
+    // everything was so the step can be undone
+    let mut moved = Vec::new();
+    for entity in to_move {
+        let mut position = world.get::<&mut Position>(entity).unwrap();
+        moved.push((entity, *position));
+
+        match direction {
+            Direction::Up => position.y -= 1,
+            Direction::Down => position.y += 1,
+            Direction::Left => position.x -= 1,
+            Direction::Right => position.x += 1,
+        }
+
+        // Fire an event for the entity that just moved
+        events.push(Event::EntityMoved(EntityMoved { entity }));
+    }
+
+    // Finally add events back into the world
+    {
+        let mut query = world.query::<&mut EventQueue>();
+        let event_queue = query.iter().next().unwrap().1;
+        event_queue.events.append(&mut events);
+    }
+
+    if moved.is_empty() {
+        None
+    } else {
+        Some(Step {
+            direction,
+            moved,
+            pushed,
+            follows_previous: false,
+        })
+    }
+}
+
+/// What a step in `direction` would move: the player, then every box it
+/// would push. Empty when the player can't move that way.
+fn entities_to_move(world: &World, direction: Direction) -> Vec<Entity> {
+    let mut to_move: Vec<Entity> = Vec::new();
+
+    // get all the movables and immovables
+    let mov: HashMap<(u16, u16), Entity> = world
+        .query::<(&Position, &Movable)>()
+        .iter()
+        .map(|t| ((t.1 .0.x, t.1 .0.y), t.0))
+        .collect::<HashMap<_, _>>();
+    let immov: HashMap<(u16, u16), Entity> = world
+        .query::<(&Position, &Immovable)>()
+        .iter()
+        .map(|t| ((t.1 .0.x, t.1 .0.y), t.0))
+        .collect::<HashMap<_, _>>();
+
+    let mut query = world.query::<&MapSize>();
+    let map_size = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&Rules>();
+    let rules = query.iter().next().unwrap().1;
+
+    for (_, (position, _player)) in world.query::<(&Position, &Player)>().iter() {
+        // Now iterate through current position to the end of the map
+        // on the correct axis and check what needs to move.
+        let (start, end, is_x) = match direction {
+            Direction::Up => (position.y, 0, false),
+            Direction::Down => (position.y, map_size.height - 1, false),
+            Direction::Left => (position.x, 0, true),
+            Direction::Right => (position.x, map_size.width - 1, true),
+        };
+
+        let range = if start < end {
+            (start..=end).collect::<Vec<_>>()
+        } else {
+            (end..=start).rev().collect::<Vec<_>>()
+        };
+
+        for x_or_y in range {
+            let pos = if is_x {
+                (x_or_y, position.y)
+            } else {
+                (position.x, x_or_y)
+            };
+
+            // find a movable
+            // if it exists, we try to move it and continue
+            // if it doesn't exist, we continue and try to find an immovable instead
+            match mov.get(&pos) {
+                Some(entity) => {
+                    to_move.push(*entity);
+
+                    // the player comes first, everything after it gets pushed,
+                    // so stop when the rules don't allow pushing that many
+                    if !rules.can_push(to_move.len() - 1) {
+                        to_move.clear();
+                        break;
+                    }
+                }
+                None => {
+                    // find an immovable
+                    // if it exists, we need to stop and not move anything
+                    // if it doesn't exist, we stop because we found a gap
+                    match immov.get(&pos) {
+                        Some(_id) => {
+                            to_move.clear();
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+
+    to_move
+}
+
+/// Whether a step in `direction` would push a box, going by the same rules
+/// as `move_player`.
+pub fn is_push(world: &World, direction: Direction) -> bool {
+    entities_to_move(world, direction).len() > 1
+}
+
+/// Takes back the last step, putting everything it moved back in place. The
+/// steps of a longer move are all taken back together.
+pub fn undo_move(world: &World) {
+    let mut query = world.query::<&mut History>();
+    let history = query.iter().next().unwrap().1;
+
+    while let Some(step) = history.undo.pop() {
+        let mut events = Vec::new();
+        for (entity, previous_position) in step.moved.iter() {
+            let mut position = world.get::<&mut Position>(*entity).unwrap();
+            *position = *previous_position;
+
+            // Moving back is still a move, so boxes landing on spots get noticed
+            events.push(Event::EntityMoved(EntityMoved { entity: *entity }));
+        }
+
+        {
+            let mut query = world.query::<&mut Gameplay>();
+            let gameplay = query.iter().next().unwrap().1;
+            gameplay.moves_count -= 1;
+            if step.pushed {
+                gameplay.pushes_count -= 1;
+            }
+        }
+
+        {
+            let mut query = world.query::<&mut EventQueue>();
+            let event_queue = query.iter().next().unwrap().1;
+            event_queue.events.append(&mut events);
+        }
+
+        let follows_previous = step.follows_previous;
+        history.redo.push(step);
+        if !follows_previous {
+            break;
+        }
+    }
+}
+
+/// Takes again the last step that was undone, or every step of the last
+/// longer move.
+pub fn redo_move(world: &World) {
+    let mut query = world.query::<&mut History>();
+    let history = query.iter().next().unwrap().1;
+
+    while let Some(undone) = history.redo.pop() {
+        let Some(mut step) = move_player(world, undone.direction) else {
+            break;
+        };
+        step.follows_previous = undone.follows_previous;
+        history.undo.push(step);
+
+        if !history
+            .redo
+            .last()
+            .is_some_and(|next| next.follows_previous)
+        {
+            break;
+        }
+    }
+}
+
+/// The shortest walk for the player to the tile at `x`, `y` that goes around
+/// everything movable or immovable, or `None` when there is no such walk.
+pub fn find_path(world: &World, x: u16, y: u16) -> Option<Vec<Direction>> {
+    let blocked = blocked_tiles(world, None);
+
+    let mut query = world.query::<&MapSize>();
+    let map_size = *query.iter().next().unwrap().1;
+
+    let mut query = world.query::<(&Position, &Player)>();
+    let (_, (start, _)) = query.iter().next()?;
+    let start = (start.x, start.y);
+
+    // Breadth first, so the first time the tile is reached is the shortest
+    let mut came_from: HashMap<(u16, u16), ((u16, u16), Direction)> = HashMap::new();
+    let mut to_visit = VecDeque::from([start]);
+
+    while let Some(current) = to_visit.pop_front() {
+        if current == (x, y) {
+            let mut path = Vec::new();
+            let mut tile = current;
+            while let Some((previous, direction)) = came_from.get(&tile) {
+                path.push(*direction);
+                tile = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for direction in DIRECTIONS {
+            let Some(next) = neighbour(&map_size, current, direction) else {
+                continue;
+            };
+
+            if next != start && !blocked.contains(&next) && !came_from.contains_key(&next) {
+                came_from.insert(next, (current, direction));
+                to_visit.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+/// The fewest steps for the player to push `the_box` onto the tile at `x`,
+/// `y` without moving anything else, or `None` when it can't be done that
+/// way.
+pub fn find_push_path(world: &World, the_box: Entity, x: u16, y: u16) -> Option<Vec<Direction>> {
+    let blocked = blocked_tiles(world, Some(the_box));
+
+    let mut query = world.query::<&MapSize>();
+    let map_size = *query.iter().next().unwrap().1;
+
+    let box_start = *world.get::<&Position>(the_box).ok()?;
+    let mut query = world.query::<(&Position, &Player)>();
+    let (_, (player_start, _)) = query.iter().next()?;
+
+    // Searches through where both the box and the player are, every step
+    // either walks the player or pushes the box along
+    let start = ((box_start.x, box_start.y), (player_start.x, player_start.y));
+    let mut came_from = HashMap::new();
+    let mut to_visit = VecDeque::from([start]);
+
+    while let Some(current) = to_visit.pop_front() {
+        let (box_tile, player_tile) = current;
+        if box_tile == (x, y) {
+            let mut path = Vec::new();
+            let mut state = current;
+            while let Some((previous, direction)) = came_from.get(&state) {
+                path.push(*direction);
+                state = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for direction in DIRECTIONS {
+            let Some(next_player) = neighbour(&map_size, player_tile, direction) else {
+                continue;
+            };
+            if blocked.contains(&next_player) {
+                continue;
+            }
+
+            let next_box = if next_player == box_tile {
+                match neighbour(&map_size, box_tile, direction) {
+                    Some(next_box) if !blocked.contains(&next_box) => next_box,
+                    _ => continue,
+                }
+            } else {
+                box_tile
+            };
+
+            let next = (next_box, next_player);
+            if next != start && !came_from.contains_key(&next) {
+                came_from.insert(next, (current, direction));
+                to_visit.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+/// The tiles taken by anything movable or immovable, apart from the player
+/// and `except`.
+fn blocked_tiles(world: &World, except: Option<Entity>) -> HashSet<(u16, u16)> {
+    world
+        .query::<(&Position, &Movable)>()
+        .without::<&Player>()
+        .iter()
+        .filter(|(entity, _)| Some(*entity) != except)
+        .map(|(_, (position, _))| (position.x, position.y))
+        .chain(
+            world
+                .query::<(&Position, &Immovable)>()
+                .iter()
+                .map(|(_, (position, _))| (position.x, position.y)),
+        )
+        .collect()
+}
+
+/// The tile next to `tile` in `direction`, if it is on the map.
+fn neighbour(map_size: &MapSize, (x, y): (u16, u16), direction: Direction) -> Option<(u16, u16)> {
+    match direction {
+        Direction::Up if y > 0 => Some((x, y - 1)),
+        Direction::Down if y + 1 < map_size.height => Some((x, y + 1)),
+        Direction::Left if x > 0 => Some((x - 1, y)),
+        Direction::Right if x + 1 < map_size.width => Some((x + 1, y)),
+        _ => None,
+    }
+}
@@ -1,108 +1,212 @@
-use crate::events::Event;
-use macroquad::{audio, prelude::*};
-use std::collections::HashMap;
-use std::fmt;
-use std::fmt::Display;
+use crate::constants::{REPLAY_SPEED, SOLVER_FRAME_TIME, SOLVER_TIME_BUDGET};
+use crate::controls::Action;
+use hecs::Entity;
+use macroquad::audio;
+use macroquad::input::KeyCode;
+use macroquad::math::Vec2;
+use macroquad::time::get_time;
+use sokoban_core::map::{Level, LevelError};
+use sokoban_core::progress::Progress;
+use sokoban_core::save::SavedLevel;
+use sokoban_core::solver::{Push, Solver, SolverStatus};
+use std::collections::{HashMap, VecDeque};
 use std::time::Duration;
 
-#[derive(Clone, Copy, Eq, Hash, PartialEq)]
-pub struct Position {
-    pub x: u8,
-    pub y: u8,
-    pub z: u8,
-}
+pub use sokoban_core::components::*;
 
-pub struct Renderable {
-    textures: Vec<Texture2D>,
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Screen {
+    #[default]
+    Level,
+    LevelSelect,
+    // Asks whether to carry on with the level saved last time
+    Resume,
+    Options,
 }
 
-pub enum RenderableKind {
-    Static,
-    Animated,
+/// The ordered list of levels being played and how far the player got.
+#[derive(Default)]
+pub struct Campaign {
+    pub levels: Vec<Level>,
+    // Levels solved so far, kept between runs
+    pub progress: Progress,
+    pub current_level: usize,
+    // Level highlighted on the level select screen
+    pub selected_level: usize,
+    pub screen: Screen,
+    // Level that should replace the current one on the next frame
+    pub level_to_load: Option<usize>,
+    // When the current level was won, used to advance after a short delay
+    pub won_at: Option<Duration>,
+    // Problems found in the last level that failed to load
+    pub level_errors: Vec<LevelError>,
+    // Level in progress saved last time, restored once its level is loaded
+    pub resume: Option<SavedLevel>,
+    // Steps in the last save written, so the level is only saved again once
+    // they change
+    pub saved_steps: Option<String>,
 }
 
-impl Renderable {
-    pub fn new_static(texture: Texture2D) -> Self {
+impl Campaign {
+    pub fn new(levels: Vec<Level>, progress: Progress) -> Self {
         Self {
-            textures: vec![texture],
+            levels,
+            progress,
+            // The first level gets loaded on the first frame
+            level_to_load: Some(0),
+            ..Default::default()
         }
     }
 
-    pub fn new_animated(paths: Vec<Texture2D>) -> Self {
-        Self {
-            textures: paths.into_iter().collect(),
-        }
+    pub fn is_completed(&self, level: usize) -> bool {
+        self.progress.is_solved(&self.levels[level].key())
     }
 
-    pub fn kind(&self) -> RenderableKind {
-        match self.textures.len() {
-            0 => panic!("invalid renderable"),
-            1 => RenderableKind::Static,
-            _ => RenderableKind::Animated,
-        }
+    /// A level can be played once the one before it has been completed.
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level == 0 || self.is_completed(level) || self.is_completed(level - 1)
     }
+}
+
+/// Playback of a list of moves in place of the player's input.
+pub struct Replay {
+    pub moves: Vec<Direction>,
+    pub next_move: usize,
+    pub active: bool,
+    pub playing: bool,
+    // Moves played per second
+    pub speed: f32,
+    pub since_last_move: Duration,
+    // Searching for the moves to play when replaying the solver's solution
+    pub search: Option<Search>,
+    // A replay or the solver moved the player since the level was started,
+    // so winning it doesn't count as solved
+    pub assisted: bool,
+}
 
-    pub fn path(&self, path_index: usize) -> Texture2D {
-        // If we get asked for a path that is larger than the
-        // number of paths we actually have, we simply mod the index
-        // with the length to get an index that is in range.
-        self.textures[path_index % self.textures.len()].clone()
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            moves: Vec::new(),
+            next_move: 0,
+            active: false,
+            playing: false,
+            speed: REPLAY_SPEED,
+            since_last_move: Duration::ZERO,
+            search: None,
+            assisted: false,
+        }
     }
 }
 
-pub struct Wall {}
+/// Steps left to walk the player to a tile that was clicked, or to push a
+/// box that was dragged there.
+#[derive(Default)]
+pub struct Walk {
+    pub steps: VecDeque<Direction>,
+    pub since_last_step: Duration,
+    // Whether the steps make up a single move, undone all at once
+    pub as_one_move: bool,
+    pub steps_taken: usize,
+    // Box held with the mouse, pushed to where it's let go
+    pub dragged_box: Option<Entity>,
+}
 
-pub struct Player {}
+#[derive(Default)]
+pub enum HintStatus {
+    #[default]
+    None,
+    Searching,
+    Push(Push),
+    // No push leads to a solution anymore
+    Unsolvable,
+    // The search gave up before finding anything
+    NotFound,
+}
 
-#[derive(PartialEq)]
-pub enum BoxColour {
-    Red,
-    Blue,
+/// A solver search spread over several frames, so the game keeps running
+/// meanwhile.
+pub struct Search {
+    pub solver: Solver,
+    // When the first frame of the search ran, as given by `get_time`
+    pub started_at: Option<f64>,
+    // Seconds from then to the end of the last frame searched
+    pub elapsed: f64,
 }
 
-impl Display for BoxColour {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_str(match self {
-            BoxColour::Red => "red",
-            BoxColour::Blue => "blue",
-        })?;
-        Ok(())
+impl Search {
+    pub fn new(solver: Solver) -> Self {
+        Self {
+            solver,
+            started_at: None,
+            elapsed: 0.0,
+        }
     }
-}
 
-pub struct Box {
-    pub colour: BoxColour,
-}
+    /// Searches for this frame's share of time. The status stays `Searching`
+    /// until the level is solved or shown to be unsolvable.
+    pub fn run_frame(&mut self) -> SolverStatus {
+        let frame_start = get_time();
+        let started_at = *self.started_at.get_or_insert(frame_start);
+        loop {
+            let status = self.solver.step(1);
+            let now = get_time();
+            if !matches!(status, SolverStatus::Searching) || now - frame_start >= SOLVER_FRAME_TIME
+            {
+                self.elapsed = now - started_at;
+                return status;
+            }
+        }
+    }
 
-pub struct BoxSpot {
-    pub colour: BoxColour,
+    pub fn is_out_of_time(&self) -> bool {
+        self.elapsed >= SOLVER_TIME_BUDGET
+    }
 }
 
-pub struct Movable;
+/// A hint for the next push, searched for over several frames.
+#[derive(Default)]
+pub struct Hint {
+    pub status: HintStatus,
+    pub search: Option<Search>,
+    // Where the boxes were when the hint was asked for, the hint no longer
+    // applies once one of them moved
+    pub boxes: Vec<(usize, BoxColour)>,
+}
 
-pub struct Immovable;
+/// Steps repeated while a move key, or a gamepad direction, is held down.
+#[derive(Default)]
+pub struct KeyRepeat {
+    // Move of the key or direction being held
+    pub action: Option<Action>,
+    pub held_for: Duration,
+    // How long the key has to be held for the next step
+    pub next_step_at: Duration,
+}
 
+/// Touch screen input: swipes, taps and the on-screen buttons.
 #[derive(Default)]
-pub enum GameplayState {
-    #[default]
-    Playing,
-    Won,
+pub struct TouchControls {
+    // The screen was touched, so the on-screen buttons are shown
+    pub active: bool,
+    // Id and start of the touch followed as a swipe
+    pub swipe: Option<(u64, Vec2)>,
 }
 
-impl Display for GameplayState {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_str(match self {
-            GameplayState::Playing => "Playing",
-            GameplayState::Won => "Won",
-        })?;
-        Ok(())
+impl TouchControls {
+    pub fn is_followed(&self, touch_id: u64) -> bool {
+        self.swipe.is_some_and(|(id, _)| id == touch_id)
     }
 }
 
+/// Where the player is in the options menu.
 #[derive(Default)]
-pub struct Gameplay {
-    pub state: GameplayState,
-    pub moves_count: u32,
+pub struct OptionsMenu {
+    pub selected: usize,
+    // Waiting for the key to bind to the selected action
+    pub rebinding: bool,
+    // The last key picked, refused because this action already uses it
+    pub conflict: Option<(KeyCode, Action)>,
 }
 
 #[derive(Default)]
@@ -110,20 +214,21 @@ pub struct Time {
     pub delta: Duration,
 }
 
-#[derive(Default)]
-pub struct EventQueue {
-    pub events: Vec<Event>,
-}
-
 #[derive(Default)]
 pub struct AudioStore {
     pub sounds: HashMap<String, std::boxed::Box<audio::Sound>>,
 }
 
 impl AudioStore {
-    pub fn play_sound(&mut self, sound: &str) {
+    pub fn play_sound(&mut self, sound: &str, volume: f32) {
         if let Some(source) = self.sounds.get_mut(sound) {
-            audio::play_sound_once(source);
+            audio::play_sound(
+                source,
+                audio::PlaySoundParams {
+                    looped: false,
+                    volume,
+                },
+            );
         }
     }
 }
@@ -1,6 +1,31 @@
+use std::time::Duration;
+
 pub const TILE_WIDTH: f32 = 32.0;
-pub const MAP_WIDTH: u8 = 8;
-pub const MAP_HEIGHT: u8 = 9;
 pub const WINDOW_WIDTH: i32 = 640;
 pub const WINDOW_HEIGHT: i32 = 480;
 pub const WINDOW_TITLE: &str = "Sokoban RS";
+pub const LEVEL_ADVANCE_DELAY: Duration = Duration::from_secs(2);
+pub const REPLAY_SPEED: f32 = 4.0;
+pub const MIN_REPLAY_SPEED: f32 = 0.5;
+pub const MAX_REPLAY_SPEED: f32 = 32.0;
+// How many seconds the hint and solver replay search before giving up,
+// and how much of each frame they take meanwhile
+pub const SOLVER_TIME_BUDGET: f64 = 10.0;
+pub const SOLVER_FRAME_TIME: f64 = 0.004;
+pub const WALK_STEP_TIME: Duration = Duration::from_millis(80);
+// How long a move key is held before it repeats, then steps per second
+pub const KEY_REPEAT_DELAY: Duration = Duration::from_millis(250);
+pub const KEY_REPEAT_RATE: f32 = 10.0;
+// Bounds of the animation speed and of the key repeat rate when it's on
+pub const MIN_ANIMATION_SPEED: f32 = 0.1;
+pub const MAX_ANIMATION_SPEED: f32 = 10.0;
+pub const MIN_KEY_REPEAT_RATE: f32 = 1.0;
+pub const MAX_KEY_REPEAT_RATE: f32 = 60.0;
+pub const MAX_KEY_REPEAT_DELAY: Duration = Duration::from_secs(2);
+// Bounds of each side of the window, from one tile to a large monitor
+pub const MIN_WINDOW_SIZE: i32 = TILE_WIDTH as i32;
+pub const MAX_WINDOW_SIZE: i32 = 8192;
+// How far the stick has to be tilted to move, and how close to the middle it
+// has to come back before it moves again
+pub const STICK_DEAD_ZONE: f32 = 0.5;
+pub const STICK_RELEASE_ZONE: f32 = 0.3;
@@ -0,0 +1,323 @@
+//! Maps the keys the player presses to the actions they stand for.
+//!
+//! Each action can have several keys. They come from one of the built-in
+//! presets, unless the player picked their own keys for the action.
+
+use macroquad::input;
+use macroquad::input::KeyCode;
+use std::collections::HashMap;
+
+/// Something the player can do with a key.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Undo,
+    Redo,
+    Restart,
+    Hint,
+    // Back to the level select screen
+    Menu,
+    Replay,
+    // Solve the level and replay the solution
+    Solve,
+    ReplayPause,
+    // Next replay move while paused
+    ReplayStep,
+    ReplayFaster,
+    ReplaySlower,
+    ReplayStop,
+    // Open the options from the level select screen
+    Options,
+}
+
+/// When an action is read. Actions read at the same time can't share a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActionContext {
+    Playing,
+    Replaying,
+    LevelSelect,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Undo,
+        Action::Redo,
+        Action::Restart,
+        Action::Hint,
+        Action::Menu,
+        Action::Replay,
+        Action::Solve,
+        Action::ReplayPause,
+        Action::ReplayStep,
+        Action::ReplayFaster,
+        Action::ReplaySlower,
+        Action::ReplayStop,
+        Action::Options,
+    ];
+
+    /// Name of the action in the settings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Restart => "restart",
+            Action::Hint => "hint",
+            Action::Menu => "menu",
+            Action::Replay => "replay",
+            Action::Solve => "solve",
+            Action::ReplayPause => "replay_pause",
+            Action::ReplayStep => "replay_step",
+            Action::ReplayFaster => "replay_faster",
+            Action::ReplaySlower => "replay_slower",
+            Action::ReplayStop => "replay_stop",
+            Action::Options => "options",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Restart => "Restart",
+            Action::Hint => "Hint",
+            Action::Menu => "Menu",
+            Action::Replay => "Replay",
+            Action::Solve => "Solve and replay",
+            Action::ReplayPause => "Pause replay",
+            Action::ReplayStep => "Next replay move",
+            Action::ReplayFaster => "Faster replay",
+            Action::ReplaySlower => "Slower replay",
+            Action::ReplayStop => "Stop replay",
+            Action::Options => "Options",
+        }
+    }
+
+    /// When the action is read. The menu is left the same way it's opened,
+    /// and it can be opened during a replay.
+    pub fn contexts(self) -> &'static [ActionContext] {
+        match self {
+            Action::Menu => &[
+                ActionContext::Playing,
+                ActionContext::Replaying,
+                ActionContext::LevelSelect,
+            ],
+            Action::ReplayPause
+            | Action::ReplayStep
+            | Action::ReplayFaster
+            | Action::ReplaySlower
+            | Action::ReplayStop => &[ActionContext::Replaying],
+            Action::Options => &[ActionContext::LevelSelect],
+            _ => &[ActionContext::Playing],
+        }
+    }
+}
+
+/// A built-in set of keys for every action.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyPreset {
+    #[default]
+    Arrows,
+    Wasd,
+    // Moves on h, j, k and l like in vi
+    Vi,
+}
+
+impl KeyPreset {
+    pub const ALL: [KeyPreset; 3] = [KeyPreset::Arrows, KeyPreset::Wasd, KeyPreset::Vi];
+
+    /// Name of the preset in the settings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyPreset::Arrows => "arrows",
+            KeyPreset::Wasd => "wasd",
+            KeyPreset::Vi => "vi",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        KeyPreset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == name)
+    }
+
+    /// The preset's keys for `action`. The arrow keys move the player in
+    /// every preset.
+    pub fn keys(self, action: Action) -> &'static [KeyCode] {
+        match (self, action) {
+            (KeyPreset::Arrows, Action::MoveUp) => &[KeyCode::Up, KeyCode::Kp8],
+            (KeyPreset::Arrows, Action::MoveDown) => &[KeyCode::Down, KeyCode::Kp2],
+            (KeyPreset::Arrows, Action::MoveLeft) => &[KeyCode::Left, KeyCode::Kp4],
+            (KeyPreset::Arrows, Action::MoveRight) => &[KeyCode::Right, KeyCode::Kp6],
+            (KeyPreset::Wasd, Action::MoveUp) => &[KeyCode::W, KeyCode::Up],
+            (KeyPreset::Wasd, Action::MoveDown) => &[KeyCode::S, KeyCode::Down],
+            (KeyPreset::Wasd, Action::MoveLeft) => &[KeyCode::A, KeyCode::Left],
+            (KeyPreset::Wasd, Action::MoveRight) => &[KeyCode::D, KeyCode::Right],
+            (KeyPreset::Vi, Action::MoveUp) => &[KeyCode::K, KeyCode::Up],
+            (KeyPreset::Vi, Action::MoveDown) => &[KeyCode::J, KeyCode::Down],
+            (KeyPreset::Vi, Action::MoveLeft) => &[KeyCode::H, KeyCode::Left],
+            (KeyPreset::Vi, Action::MoveRight) => &[KeyCode::L, KeyCode::Right],
+            (KeyPreset::Vi, Action::Undo) => &[KeyCode::U, KeyCode::Z, KeyCode::Backspace],
+            // H moves left in vi, so the hint moves to ? on the same key as /
+            (KeyPreset::Vi, Action::Hint) => &[KeyCode::Slash],
+            (_, Action::Undo) => &[KeyCode::Z, KeyCode::Backspace],
+            (_, Action::Redo) => &[KeyCode::Y],
+            (_, Action::Restart) => &[KeyCode::R],
+            (_, Action::Hint) => &[KeyCode::H],
+            (_, Action::Menu) => &[KeyCode::Escape],
+            (_, Action::Replay) => &[KeyCode::P],
+            (_, Action::Solve) => &[KeyCode::O],
+            (_, Action::ReplayPause) => &[KeyCode::Space],
+            (_, Action::ReplayStep) => &[KeyCode::Period],
+            (_, Action::ReplayFaster) => &[KeyCode::Equal],
+            (_, Action::ReplaySlower) => &[KeyCode::Minus],
+            (_, Action::ReplayStop) => &[KeyCode::S],
+            (_, Action::Options) => &[KeyCode::O],
+        }
+    }
+}
+
+/// The keys for each action: the preset's, except for the actions the player
+/// picked keys for.
+#[derive(Clone, Debug, Default)]
+pub struct KeyBindings {
+    pub preset: KeyPreset,
+    pub overrides: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        match self.overrides.get(&action) {
+            Some(keys) => keys,
+            None => self.preset.keys(action),
+        }
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|key| input::is_key_pressed(*key))
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|key| input::is_key_down(*key))
+    }
+
+    /// Another action that `key` already stands for when `action` is read,
+    /// which would take the key first.
+    pub fn conflict(&self, action: Action, key: KeyCode) -> Option<Action> {
+        Action::ALL.into_iter().find(|other| {
+            *other != action
+                && self.keys(*other).contains(&key)
+                && other
+                    .contexts()
+                    .iter()
+                    .any(|context| action.contexts().contains(context))
+        })
+    }
+
+    /// The keys for `action` as shown to the player, like "Z, Backspace".
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self.keys(action).iter().map(|key| key_name(*key)).collect();
+        names.join(", ")
+    }
+}
+
+/// Keys that can be bound to an action.
+pub const BINDABLE_KEYS: [KeyCode; 71] = [
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::LeftBracket,
+    KeyCode::RightBracket,
+    KeyCode::Backslash,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::GraveAccent,
+    KeyCode::Escape,
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .into_iter()
+        .find(|key| key_name(*key).eq_ignore_ascii_case(name))
+}
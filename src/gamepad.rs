@@ -0,0 +1,186 @@
+//! Gamepad input, turned into the same actions as the keyboard.
+//!
+//! The D-pad and the left stick move the player, the face buttons undo, redo,
+//! restart and ask for a hint and Start goes back to the menu. A direction
+//! held down repeats like a held move key. Gamepads are
+//! read with gilrs on native builds only, the web build has no gamepad
+//! support.
+
+use crate::constants::*;
+use crate::controls::Action;
+use std::collections::HashSet;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// The action of each button, buttons that aren't listed are only used in
+/// the menus.
+const BUTTON_ACTIONS: [(Button, Action); 9] = [
+    (Button::DPadUp, Action::MoveUp),
+    (Button::DPadDown, Action::MoveDown),
+    (Button::DPadLeft, Action::MoveLeft),
+    (Button::DPadRight, Action::MoveRight),
+    (Button::South, Action::Undo),
+    (Button::East, Action::Redo),
+    (Button::West, Action::Restart),
+    (Button::North, Action::Hint),
+    (Button::Start, Action::Menu),
+];
+
+/// What the connected gamepads did this frame.
+#[derive(Default)]
+pub struct Gamepads {
+    // Behind a mutex only because components have to be Sync, it's never
+    // shared
+    #[cfg(not(target_arch = "wasm32"))]
+    gilrs: Option<Mutex<gilrs::Gilrs>>,
+    // Buttons pressed down this frame
+    pressed: HashSet<Button>,
+    // Buttons down right now, whenever they were pressed
+    held: HashSet<Button>,
+    // Move the left stick was tilted for, held until it's back near the
+    // middle so one tilt is one step
+    stick: Option<Action>,
+    stick_tilted: bool,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            gilrs: gilrs::Gilrs::new()
+                .map_err(|error| eprintln!("Gamepads unavailable: {}", error))
+                .ok()
+                .map(Mutex::new),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_button_pressed(&self, button: Button) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// Whether a button for `action` was pressed, or the stick was tilted
+    /// for it, this frame.
+    pub fn is_pressed(&self, action: Action) -> bool {
+        (self.stick_tilted && self.stick == Some(action))
+            || BUTTON_ACTIONS.iter().any(|(button, button_action)| {
+                *button_action == action && self.is_button_pressed(*button)
+            })
+    }
+
+    /// Whether a button for `action` is held down, or the stick is tilted
+    /// for it, whenever that started.
+    pub fn is_down(&self, action: Action) -> bool {
+        self.stick == Some(action)
+            || BUTTON_ACTIONS.iter().any(|(button, button_action)| {
+                *button_action == action && self.held.contains(button)
+            })
+    }
+
+    /// Reads what happened on the gamepads since the last frame.
+    pub fn update(&mut self) {
+        self.pressed.clear();
+        self.stick_tilted = false;
+
+        let (x, y) = self.read_events();
+
+        // Past the dead zone the stick moves along the axis it leans the
+        // most towards, it has to come back inside the release zone before
+        // it moves again
+        let tilt = x.abs().max(y.abs());
+        let stick = if tilt >= STICK_DEAD_ZONE {
+            Some(if x.abs() > y.abs() {
+                if x > 0.0 {
+                    Action::MoveRight
+                } else {
+                    Action::MoveLeft
+                }
+            } else if y > 0.0 {
+                Action::MoveUp
+            } else {
+                Action::MoveDown
+            })
+        } else if tilt < STICK_RELEASE_ZONE {
+            None
+        } else {
+            self.stick
+        };
+
+        self.stick_tilted = stick.is_some() && stick != self.stick;
+        self.stick = stick;
+    }
+
+    /// Records the buttons pressed and released since the last frame and
+    /// returns where the left stick is, up being positive.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_events(&mut self) -> (f32, f32) {
+        let Some(gilrs) = self.gilrs.as_mut().and_then(|gilrs| gilrs.get_mut().ok()) else {
+            return (0.0, 0.0);
+        };
+
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            match event {
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = read_button(button) {
+                        self.pressed.insert(button);
+                        self.held.insert(button);
+                    }
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = read_button(button) {
+                        self.held.remove(&button);
+                    }
+                }
+                // Its buttons won't be released anymore
+                gilrs::EventType::Disconnected => self.held.clear(),
+                _ => (),
+            }
+        }
+
+        // The first gamepad with its stick out of the middle steers
+        gilrs
+            .gamepads()
+            .map(|(_, gamepad)| {
+                (
+                    gamepad.value(gilrs::Axis::LeftStickX),
+                    gamepad.value(gilrs::Axis::LeftStickY),
+                )
+            })
+            .find(|(x, y)| x.abs().max(y.abs()) >= STICK_RELEASE_ZONE)
+            .unwrap_or((0.0, 0.0))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read_events(&mut self) -> (f32, f32) {
+        (0.0, 0.0)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_button(button: gilrs::Button) -> Option<Button> {
+    match button {
+        gilrs::Button::South => Some(Button::South),
+        gilrs::Button::East => Some(Button::East),
+        gilrs::Button::North => Some(Button::North),
+        gilrs::Button::West => Some(Button::West),
+        gilrs::Button::Start => Some(Button::Start),
+        gilrs::Button::DPadUp => Some(Button::DPadUp),
+        gilrs::Button::DPadDown => Some(Button::DPadDown),
+        gilrs::Button::DPadLeft => Some(Button::DPadLeft),
+        gilrs::Button::DPadRight => Some(Button::DPadRight),
+        _ => None,
+    }
+}
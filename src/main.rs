@@ -1,4 +1,5 @@
-use constants::{WINDOW_HEIGHT, WINDOW_TITLE, WINDOW_WIDTH};
+use components::{Campaign, Screen};
+use constants::WINDOW_TITLE;
 use hecs::World;
 use macroquad::audio::{self, Sound};
 use macroquad::conf::UpdateTrigger;
@@ -8,28 +9,35 @@ use std::collections::HashMap;
 #[cfg(not(target_arch = "wasm32"))]
 use std::env;
 
+use sokoban_core::map;
+
 mod components;
 mod constants;
+mod controls;
 mod entities;
-mod events;
-mod map;
+mod gamepad;
+mod settings;
+mod solution;
+mod storage;
 mod systems;
 
 fn window_conf() -> macroquad::conf::Conf {
+    let settings = settings::load_settings();
+
     macroquad::conf::Conf {
         miniquad_conf: macroquad::prelude::Conf {
             window_title: WINDOW_TITLE.to_string(),
-            window_width: WINDOW_WIDTH,
-            window_height: WINDOW_HEIGHT,
+            window_width: settings.window_width,
+            window_height: settings.window_height,
             high_dpi: false,
-            fullscreen: false,
+            fullscreen: settings.fullscreen,
             sample_count: 1,
             window_resizable: false,
             icon: None,
             ..Default::default()
         },
         update_on: Some(UpdateTrigger::default()),
-        default_filter_mode: FilterMode::Nearest,
+        default_filter_mode: settings.filter_mode,
     }
 }
 
@@ -48,30 +56,104 @@ fn set_current_path() {
     set_pc_assets_folder("resources");
 }
 
+/// Reads the level file given as the first command line argument, if any.
+/// `.slc` level packs are played in full, anything else is read as XSB.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_level_argument() -> Option<Vec<map::Level>> {
+    let level_path = env::args().nth(1)?;
+    let contents = std::fs::read_to_string(&level_path)
+        .unwrap_or_else(|_| panic!("failed to read level file {}.", level_path));
+
+    if level_path.ends_with(".slc") {
+        let mut pack = map::load_level_pack(&contents)
+            .unwrap_or_else(|error| panic!("failed to load {}: {}.", level_path, error));
+        // A pack without a title is told apart from others by its path
+        for level in pack.levels.iter_mut() {
+            level.pack.get_or_insert_with(|| level_path.clone());
+        }
+        println!(
+            "Loaded level pack {} with {} levels",
+            pack.title.as_deref().unwrap_or(&level_path),
+            pack.levels.len()
+        );
+        Some(pack.levels)
+    } else {
+        Some(vec![map::Level::from_xsb(level_path, contents)])
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_level_argument() -> Option<Vec<map::Level>> {
+    None
+}
+
 #[macroquad::main(window_conf)]
 async fn main() {
     set_current_path();
 
     let texture_atlas = make_texture_atlas().await;
     let sounds_atlas = make_sound_atlas().await;
+    let levels = make_campaign().await;
     let mut world = World::new();
 
-    entities::create_gameplay(&mut world);
+    sokoban_core::entities::create_gameplay(&mut world);
+    sokoban_core::entities::create_map_size(&mut world);
+    sokoban_core::entities::create_history(&mut world);
+    sokoban_core::entities::create_rules(&mut world);
+    sokoban_core::entities::create_event_queue(&mut world);
+    sokoban_core::entities::create_dead_squares(&mut world);
+    sokoban_core::entities::create_deadlocks(&mut world);
+    entities::create_replay(&mut world);
+    entities::create_walk(&mut world);
+    entities::create_key_repeat(&mut world);
+    entities::create_hint(&mut world);
     entities::create_time(&mut world);
-    entities::create_event_queue(&mut world);
     entities::create_audio_store(&mut world);
+    entities::create_settings(&mut world, settings::load_settings());
+    entities::create_options_menu(&mut world);
+    entities::create_gamepads(&mut world);
+    entities::create_touch_controls(&mut world);
+    entities::create_campaign(&mut world, levels, systems::save::read_progress());
+    systems::save::offer_resume(&world);
+
+    load_sounds(&mut world, &sounds_atlas);
 
-    map::initialize_level(&mut world, &texture_atlas, &sounds_atlas);
+    // Touches are handled on their own, as swipes and taps
+    simulate_mouse_with_touch(false);
 
     loop {
-        systems::input::run_input(&world);
-        systems::gameplay::run_gameplay_state(&world);
-        systems::events::run_process_events(&mut world);
-        systems::rendering::run_rendering(&world);
+        systems::input::run_gamepads(&world);
+        systems::campaign::run_campaign(&mut world);
+        systems::campaign::run_campaign_input(&world);
+
+        let frame_time = std::time::Duration::from_secs_f32(get_frame_time());
+
+        let screen = world.query::<&Campaign>().iter().next().unwrap().1.screen;
+        match screen {
+            Screen::Level => {
+                systems::input::run_input(&world);
+                systems::hint::run_hint(&world);
+                sokoban_core::systems::gameplay::run_gameplay_state(&world);
+                sokoban_core::systems::gameplay::run_gameplay_timer(&world, frame_time);
+                systems::events::run_process_events(&mut world);
+                systems::save::run_autosave(&world);
+                systems::rendering::run_rendering(&world, &texture_atlas);
+            }
+            Screen::LevelSelect => {
+                systems::rendering::run_level_select_rendering(&world);
+            }
+            Screen::Resume => {
+                systems::rendering::run_resume_rendering(&world);
+            }
+            Screen::Options => {
+                systems::options::run_options_input(&world, &texture_atlas);
+                systems::rendering::run_options_rendering(&world);
+            }
+        }
 
         let mut query = world.query::<&mut crate::components::Time>();
         let time = query.iter().next().unwrap().1;
-        time.delta += std::time::Duration::from_secs_f32(get_frame_time());
+        time.delta += frame_time;
 
         next_frame().await;
     }
@@ -148,7 +230,24 @@ async fn make_texture_atlas() -> HashMap<String, Texture2D> {
     ]);
 
     build_textures_atlas();
-    return texture_atlas;
+    texture_atlas
+}
+
+/// The levels to play: the ones given on the command line, or the built-in
+/// level followed by the bundled level pack.
+async fn make_campaign() -> Vec<map::Level> {
+    if let Some(levels) = read_level_argument() {
+        return levels;
+    }
+
+    let pack = load_string("levels/campaign.slc")
+        .await
+        .expect("Failed to load levels.");
+    let pack = map::load_level_pack(&pack).expect("Failed to parse levels.");
+
+    let mut levels = vec![map::default_level()];
+    levels.extend(pack.levels);
+    levels
 }
 
 async fn make_sound_atlas() -> HashMap<String, Sound> {
@@ -165,6 +264,12 @@ async fn make_sound_atlas() -> HashMap<String, Sound> {
                 .await
                 .expect("Failed to load sound."),
         ),
+        (
+            String::from("stuck"),
+            audio::load_sound("sounds/stuck.wav")
+                .await
+                .expect("Failed to load sound."),
+        ),
         (
             String::from("wall"),
             audio::load_sound("sounds/wall.wav")
@@ -173,5 +278,25 @@ async fn make_sound_atlas() -> HashMap<String, Sound> {
         ),
     ]);
 
-    return sound_atlas;
+    sound_atlas
+}
+
+pub fn load_sounds(world: &mut World, sounds_atlas: &HashMap<String, Sound>) {
+    let mut query = world.query::<&mut components::AudioStore>();
+    let audio_store = query.iter().next().unwrap().1;
+
+    let sounds = ["correct", "incorrect", "stuck", "wall"];
+
+    for sound in sounds.iter() {
+        let sound_name = *sound;
+        match sounds_atlas.get(sound_name) {
+            Some(sound) => {
+                audio_store
+                    .sounds
+                    .insert(sound_name.to_string(), Box::new(sound.clone()));
+            }
+            // The game is still playable without sounds, so only report it
+            None => eprintln!("Missing sound {}", sound_name),
+        }
+    }
 }
@@ -0,0 +1,229 @@
+//! Settings the player can change, kept between runs.
+//!
+//! They are saved as `name = value` lines, lines starting with `#` are
+//! comments. Anything missing or that can't be read keeps its default, so an
+//! old or hand edited file still works.
+
+use crate::constants::*;
+use crate::controls::{key_name, parse_key, Action, KeyBindings, KeyPreset};
+use crate::storage;
+use macroquad::texture::FilterMode;
+use std::time::Duration;
+
+// Storage key of the settings
+const SETTINGS_KEY: &str = "settings";
+
+pub struct Settings {
+    pub window_width: i32,
+    pub window_height: i32,
+    pub fullscreen: bool,
+    pub filter_mode: FilterMode,
+    // From 0 for silence to 1 for full volume
+    pub volume: f32,
+    // How much faster than normal sprites animate and the player walks
+    pub animation_speed: f32,
+    // How long a move key is held before it repeats
+    pub repeat_delay: Duration,
+    // Steps per second while a move key is held, 0 to not repeat
+    pub repeat_rate: f32,
+    // Whether a held move key stops at a box instead of pushing it
+    pub repeat_stops_at_push: bool,
+    // Whether undo and restart buttons are shown once the screen is touched
+    pub touch_buttons: bool,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_width: WINDOW_WIDTH,
+            window_height: WINDOW_HEIGHT,
+            fullscreen: false,
+            filter_mode: FilterMode::Nearest,
+            volume: 1.0,
+            animation_speed: 1.0,
+            repeat_delay: KEY_REPEAT_DELAY,
+            repeat_rate: KEY_REPEAT_RATE,
+            repeat_stops_at_push: false,
+            touch_buttons: true,
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# Sokoban RS settings, also changed from the options menu\n");
+        text.push_str(&format!("window_width = {}\n", self.window_width));
+        text.push_str(&format!("window_height = {}\n", self.window_height));
+        text.push_str(&format!("fullscreen = {}\n", self.fullscreen));
+        text.push_str(&format!(
+            "filter_mode = {}\n",
+            filter_mode_name(self.filter_mode)
+        ));
+        text.push_str(&format!("volume = {}\n", self.volume));
+        text.push_str(&format!("animation_speed = {}\n", self.animation_speed));
+        text.push_str(&format!(
+            "repeat_delay = {}\n",
+            self.repeat_delay.as_millis()
+        ));
+        text.push_str(&format!("repeat_rate = {}\n", self.repeat_rate));
+        text.push_str(&format!(
+            "repeat_stops_at_push = {}\n",
+            self.repeat_stops_at_push
+        ));
+        text.push_str(&format!("touch_buttons = {}\n", self.touch_buttons));
+        text.push_str(&format!(
+            "key_preset = {}\n",
+            self.key_bindings.preset.name()
+        ));
+        // Only the keys picked by the player, the others follow the preset
+        for action in Action::ALL {
+            if let Some(keys) = self.key_bindings.overrides.get(&action) {
+                let names: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+                text.push_str(&format!("key.{} = {}\n", action.name(), names.join(" ")));
+            }
+        }
+        text
+    }
+
+    /// Reads settings, reporting the lines that can't be read and leaving
+    /// their settings as they are by default.
+    pub fn from_text(text: &str) -> Self {
+        let mut settings = Settings::default();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let read = line.split_once('=').and_then(|(name, value)| {
+                let (name, value) = (name.trim(), value.trim());
+                match name {
+                    "window_width" => {
+                        settings.window_width = clamp_window_size(value.parse().ok()?)?
+                    }
+                    "window_height" => {
+                        settings.window_height = clamp_window_size(value.parse().ok()?)?
+                    }
+                    "fullscreen" => settings.fullscreen = value.parse().ok()?,
+                    "filter_mode" => settings.filter_mode = parse_filter_mode(value)?,
+                    "volume" => settings.volume = value.parse::<f32>().ok()?.clamp(0.0, 1.0),
+                    "animation_speed" => {
+                        settings.animation_speed = clamp_animation_speed(value.parse().ok()?)?
+                    }
+                    "repeat_delay" => {
+                        settings.repeat_delay =
+                            Duration::from_millis(value.parse().ok()?).min(MAX_KEY_REPEAT_DELAY)
+                    }
+                    "repeat_rate" => settings.repeat_rate = clamp_repeat_rate(value.parse().ok()?)?,
+                    "repeat_stops_at_push" => settings.repeat_stops_at_push = value.parse().ok()?,
+                    "touch_buttons" => settings.touch_buttons = value.parse().ok()?,
+                    "key_preset" => settings.key_bindings.preset = KeyPreset::from_name(value)?,
+                    _ => {
+                        let action = Action::ALL
+                            .into_iter()
+                            .find(|action| name.strip_prefix("key.") == Some(action.name()))?;
+                        let keys = value
+                            .split_whitespace()
+                            .map(parse_key)
+                            .collect::<Option<Vec<_>>>()
+                            .filter(|keys| !keys.is_empty())?;
+                        settings.key_bindings.overrides.insert(action, keys);
+                    }
+                }
+                Some(())
+            });
+
+            if read.is_none() {
+                eprintln!("Ignoring setting \"{}\"", line);
+            }
+        }
+
+        settings
+    }
+}
+
+/// Reads the saved settings, or the defaults when there are none.
+pub fn load_settings() -> Settings {
+    storage::read(SETTINGS_KEY)
+        .map(|text| Settings::from_text(&text))
+        .unwrap_or_default()
+}
+
+pub fn save_settings(settings: &Settings) {
+    storage::write(SETTINGS_KEY, &settings.to_text());
+}
+
+/// Keeps a window width or height within the sizes the game can be shown
+/// at, `None` when it isn't a positive number.
+pub fn clamp_window_size(size: i32) -> Option<i32> {
+    (size > 0).then(|| size.clamp(MIN_WINDOW_SIZE, MAX_WINDOW_SIZE))
+}
+
+/// Keeps an animation speed within the speeds the game can run at, `None`
+/// when it isn't a positive number.
+pub fn clamp_animation_speed(speed: f32) -> Option<f32> {
+    (speed > 0.0 && speed.is_finite())
+        .then(|| speed.clamp(MIN_ANIMATION_SPEED, MAX_ANIMATION_SPEED))
+}
+
+/// Keeps a key repeat rate within the rates the game can run at, 0 staying
+/// off. `None` when it's negative or not a number.
+pub fn clamp_repeat_rate(rate: f32) -> Option<f32> {
+    match rate {
+        0.0 => Some(0.0),
+        rate if rate > 0.0 && rate.is_finite() => {
+            Some(rate.clamp(MIN_KEY_REPEAT_RATE, MAX_KEY_REPEAT_RATE))
+        }
+        _ => None,
+    }
+}
+
+pub fn filter_mode_name(filter_mode: FilterMode) -> &'static str {
+    match filter_mode {
+        FilterMode::Nearest => "nearest",
+        FilterMode::Linear => "linear",
+    }
+}
+
+fn parse_filter_mode(name: &str) -> Option<FilterMode> {
+    match name {
+        "nearest" => Some(FilterMode::Nearest),
+        "linear" => Some(FilterMode::Linear),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_values_are_brought_back_in_range() {
+        let settings = Settings::from_text(
+            "window_width = 100000\nwindow_height = 1\nvolume = 3\nanimation_speed = 50\n\
+             repeat_delay = 999999\nrepeat_rate = 1000\n",
+        );
+        assert_eq!(settings.window_width, MAX_WINDOW_SIZE);
+        assert_eq!(settings.window_height, MIN_WINDOW_SIZE);
+        assert_eq!(settings.volume, 1.0);
+        assert_eq!(settings.animation_speed, MAX_ANIMATION_SPEED);
+        assert_eq!(settings.repeat_delay, MAX_KEY_REPEAT_DELAY);
+        assert_eq!(settings.repeat_rate, MAX_KEY_REPEAT_RATE);
+    }
+
+    #[test]
+    fn values_that_cant_be_used_keep_their_default() {
+        let settings = Settings::from_text(
+            "window_width = 0\nwindow_height = -480\nanimation_speed = -1\n\
+             repeat_delay = -5\nrepeat_rate = NaN\n",
+        );
+        let default = Settings::default();
+        assert_eq!(settings.window_width, default.window_width);
+        assert_eq!(settings.window_height, default.window_height);
+        assert_eq!(settings.animation_speed, default.animation_speed);
+        assert_eq!(settings.repeat_delay, default.repeat_delay);
+        assert_eq!(settings.repeat_rate, default.repeat_rate);
+    }
+}
@@ -0,0 +1,68 @@
+use sokoban_core::map::Level;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+const SOLUTIONS_DIR: &str = "solutions";
+
+/// Prints a solution in LURD notation and saves it to
+/// `solutions/<collection>/<level id>.lurd`, so it can be shared with other
+/// Sokoban tools.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_solution(level: &Level, solution: &str) {
+    println!("Solution for {}: {}", level.name(), solution);
+
+    let path = solution_path(level);
+    let result = fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(&path, format!("{}\n", solution)));
+
+    match result {
+        Ok(()) => println!("Solution saved to {}", path.display()),
+        Err(error) => eprintln!("Failed to save {}: {}", path.display(), error),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn export_solution(level: &Level, solution: &str) {
+    // There is no file system to save to, the browser console will have to do
+    macroquad::logging::info!("Solution for {}: {}", level.name(), solution);
+}
+
+/// Reads back the solution saved for a level by `export_solution`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_solution(level: &Level) -> Option<String> {
+    fs::read_to_string(solution_path(level)).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_solution(_level: &Level) -> Option<String> {
+    None
+}
+
+/// Where the solution of a level is saved. Levels that aren't part of a
+/// collection are saved straight in the solutions directory.
+#[cfg(not(target_arch = "wasm32"))]
+fn solution_path(level: &Level) -> PathBuf {
+    let mut path = PathBuf::from(SOLUTIONS_DIR);
+    if let Some(pack) = &level.pack {
+        path.push(file_name(pack));
+    }
+    path.push(format!("{}.lurd", file_name(&level.id)));
+    path
+}
+
+/// Turns a level id or collection title, which can be anything including a
+/// path, into something safe to use as a file name.
+#[cfg(not(target_arch = "wasm32"))]
+fn file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
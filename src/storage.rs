@@ -0,0 +1,75 @@
+//! Small text files the game keeps between runs, like the level in progress.
+//! They are files in the `saves` folder on native and entries in the
+//! browser's local storage on wasm32.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVES_DIR: &str = "saves";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(key: &str) -> PathBuf {
+    PathBuf::from(SAVES_DIR).join(format!("{}.txt", key))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(key: &str) -> Option<String> {
+    fs::read_to_string(path(key)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(key: &str, value: &str) {
+    let result = fs::create_dir_all(SAVES_DIR).and_then(|_| fs::write(path(key), value));
+    if let Err(error) = result {
+        eprintln!("Failed to save {}: {}", path(key).display(), error);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(key: &str) {
+    // Nothing to do when there was nothing saved
+    let _ = fs::remove_file(path(key));
+}
+
+// Implemented by the `sokoban_storage` plugin in `storage.js`
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn sokoban_storage_length(key: *const u8, key_length: usize) -> i32;
+    fn sokoban_storage_read(key: *const u8, key_length: usize, value: *mut u8, value_length: usize);
+    fn sokoban_storage_write(
+        key: *const u8,
+        key_length: usize,
+        value: *const u8,
+        value_length: usize,
+    );
+    fn sokoban_storage_remove(key: *const u8, key_length: usize);
+}
+
+/// Lets the JS side check it matches this version of the plugin.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+pub extern "C" fn sokoban_storage_crate_version() -> u32 {
+    1
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(key: &str) -> Option<String> {
+    // A negative length means there is nothing stored for the key
+    let length = unsafe { sokoban_storage_length(key.as_ptr(), key.len()) };
+    let length = usize::try_from(length).ok()?;
+
+    let mut value = vec![0; length];
+    unsafe { sokoban_storage_read(key.as_ptr(), key.len(), value.as_mut_ptr(), length) };
+    String::from_utf8(value).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(key: &str, value: &str) {
+    unsafe { sokoban_storage_write(key.as_ptr(), key.len(), value.as_ptr(), value.len()) };
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove(key: &str) {
+    unsafe { sokoban_storage_remove(key.as_ptr(), key.len()) };
+}
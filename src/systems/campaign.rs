@@ -0,0 +1,248 @@
+use crate::components::*;
+use crate::constants::*;
+use crate::controls::Action;
+use crate::gamepad::{Button, Gamepads};
+use crate::settings::Settings;
+use crate::solution;
+use crate::systems::save;
+use hecs::World;
+use macroquad::input;
+use macroquad::input::KeyCode;
+use sokoban_core::map;
+use sokoban_core::save::restore_level;
+
+pub fn run_campaign_input(world: &World) {
+    let mut query = world.query::<&Settings>();
+    let settings = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&Gamepads>();
+    let gamepads = query.iter().next().unwrap().1;
+
+    // The menus take the arrow keys, Enter and the menu keys, or the D-pad,
+    // South to confirm and Start to go back. Going back to the level happens
+    // before its input is read on the same frame, so it can't be on a button
+    // the level uses.
+    let up = input::is_key_pressed(KeyCode::Up) || gamepads.is_pressed(Action::MoveUp);
+    let down = input::is_key_pressed(KeyCode::Down) || gamepads.is_pressed(Action::MoveDown);
+    let confirm =
+        input::is_key_pressed(KeyCode::Enter) || gamepads.is_button_pressed(Button::South);
+    let back = settings.key_bindings.is_pressed(Action::Menu) || gamepads.is_pressed(Action::Menu);
+
+    let mut query = world.query::<&mut Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
+    match campaign.screen {
+        Screen::Level => {
+            if back {
+                campaign.selected_level = campaign.current_level;
+                campaign.screen = Screen::LevelSelect;
+            }
+        }
+        Screen::Resume => {
+            let tapped = input::touches()
+                .iter()
+                .any(|touch| touch.phase == input::TouchPhase::Started);
+            if confirm || tapped {
+                campaign.level_to_load = Some(campaign.selected_level);
+            } else if back {
+                // Start over from the first level
+                campaign.resume = None;
+                campaign.level_to_load = Some(0);
+            }
+        }
+        // Handled by the options system
+        Screen::Options => (),
+        Screen::LevelSelect => {
+            if up && campaign.selected_level > 0 {
+                campaign.selected_level -= 1;
+            } else if down && campaign.selected_level + 1 < campaign.levels.len() {
+                campaign.selected_level += 1;
+            } else if confirm && campaign.is_unlocked(campaign.selected_level) {
+                campaign.level_to_load = Some(campaign.selected_level);
+            } else if settings.key_bindings.is_pressed(Action::Options) {
+                campaign.screen = Screen::Options;
+            } else if back {
+                // Only go back when there is a level loaded to go back to
+                if world.query::<&Player>().iter().next().is_some() {
+                    campaign.level_errors.clear();
+                    campaign.screen = Screen::Level;
+                }
+            }
+        }
+    }
+}
+
+pub fn run_campaign(world: &mut World) {
+    let level_to_load = {
+        let mut query = world.query::<&Time>();
+        let now = query.iter().next().unwrap().1.delta;
+
+        let mut query = world.query::<&Gameplay>();
+        let gameplay = query.iter().next().unwrap().1;
+
+        let assisted = world.query::<&Replay>().iter().next().unwrap().1.assisted;
+
+        let mut query = world.query::<&mut Campaign>();
+        let campaign = query.iter().next().unwrap().1;
+
+        // Once a level is won, mark it as completed and move on to the next
+        // one after a short delay. The last level, or one that stays locked
+        // after a replay or the solver won this one, goes back to level
+        // select.
+        if campaign.screen == Screen::Level && matches!(gameplay.state, GameplayState::Won) {
+            match campaign.won_at {
+                None if assisted => campaign.won_at = Some(now),
+                None => {
+                    let current_level = campaign.current_level;
+                    campaign.won_at = Some(now);
+
+                    let mut query = world.query::<&History>();
+                    let solution = query.iter().next().unwrap().1.to_lurd();
+                    solution::export_solution(&campaign.levels[current_level], &solution);
+
+                    campaign.progress.record_win(
+                        &campaign.levels[current_level].key(),
+                        gameplay.moves_count,
+                        gameplay.pushes_count,
+                        gameplay.elapsed,
+                        &solution,
+                    );
+                    save::write_progress(&campaign.progress);
+                }
+                Some(won_at) if now - won_at >= LEVEL_ADVANCE_DELAY => {
+                    let next_level = campaign.current_level + 1;
+                    if next_level < campaign.levels.len() && campaign.is_unlocked(next_level) {
+                        campaign.level_to_load = Some(next_level);
+                    } else {
+                        campaign.selected_level = campaign.current_level;
+                        campaign.screen = Screen::LevelSelect;
+                    }
+                }
+                Some(_) => (),
+            }
+        } else if campaign.screen == Screen::Level {
+            // The win was undone before moving on
+            campaign.won_at = None;
+        }
+
+        campaign
+            .level_to_load
+            .take()
+            .map(|level_to_load| (level_to_load, campaign.levels[level_to_load].clone()))
+    };
+
+    let Some((level_to_load, level)) = level_to_load else {
+        return;
+    };
+
+    println!(
+        "Loading level {} by {} ({})",
+        level.name(),
+        level.author.as_deref().unwrap_or("unknown"),
+        level.copyright.as_deref().unwrap_or("no copyright"),
+    );
+
+    // Swap the level entities and start the new level from scratch, a level
+    // that fails to load leaves the current one in place.
+    let result = map::load_level(world, &level);
+
+    let mut query = world.query::<&mut Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
+    if let Err(errors) = result {
+        for error in errors.iter() {
+            eprintln!("Level {}: {}", level.name(), error);
+        }
+        campaign.selected_level = level_to_load;
+        campaign.screen = Screen::LevelSelect;
+        campaign.level_errors = errors;
+        campaign.resume = None;
+        return;
+    }
+
+    // Pick up where the saved level was left, or start it over when the
+    // save doesn't fit the level anymore
+    if let Some(saved) = campaign.resume.take() {
+        if let Err(error) = restore_level(world, &level, &saved) {
+            eprintln!("Can't resume {}: {}", level.name(), error);
+            map::restart_level(world);
+        }
+    }
+
+    campaign.current_level = level_to_load;
+    campaign.selected_level = level_to_load;
+    campaign.screen = Screen::Level;
+    campaign.won_at = None;
+    campaign.level_errors.clear();
+    campaign.saved_steps = None;
+
+    let mut query = world.query::<&mut Replay>();
+    let replay = query.iter().next().unwrap().1;
+    *replay = Replay::default();
+
+    let mut query = world.query::<&mut Walk>();
+    let walk = query.iter().next().unwrap().1;
+    *walk = Walk::default();
+
+    let mut query = world.query::<&mut KeyRepeat>();
+    let key_repeat = query.iter().next().unwrap().1;
+    *key_repeat = KeyRepeat::default();
+
+    let mut query = world.query::<&mut Hint>();
+    let hint = query.iter().next().unwrap().1;
+    *hint = Hint::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::*;
+    use crate::systems::replay;
+    use sokoban_core::lurd::parse_lurd;
+    use sokoban_core::map::Level;
+    use sokoban_core::progress::Progress;
+    use sokoban_core::solver::{SolverBudget, SolverStatus};
+    use sokoban_core::systems::gameplay::run_gameplay_state;
+
+    #[test]
+    fn solver_win_is_not_recorded() {
+        let level = Level::from_xsb(String::from("test"), String::from("#####\n#@$.#\n#####"));
+        let mut world = World::new();
+        sokoban_core::entities::create_gameplay(&mut world);
+        sokoban_core::entities::create_map_size(&mut world);
+        sokoban_core::entities::create_history(&mut world);
+        sokoban_core::entities::create_rules(&mut world);
+        sokoban_core::entities::create_event_queue(&mut world);
+        sokoban_core::entities::create_dead_squares(&mut world);
+        sokoban_core::entities::create_deadlocks(&mut world);
+        create_replay(&mut world);
+        create_walk(&mut world);
+        create_key_repeat(&mut world);
+        create_hint(&mut world);
+        create_time(&mut world);
+        create_campaign(&mut world, vec![level], Progress::default());
+        run_campaign(&mut world);
+
+        // Search and play the solution the way `run_replay` does
+        replay::start_solver_replay(&world);
+        {
+            let mut query = world.query::<&mut Replay>();
+            let replay_state = query.iter().next().unwrap().1;
+            let mut solver = replay_state.search.take().unwrap().solver;
+            let SolverStatus::Solved(solution) = solver.solve(SolverBudget::default()) else {
+                panic!("level not solved");
+            };
+            replay_state.moves = parse_lurd(&solution.lurd).unwrap();
+            while replay_state.active {
+                replay::play_next_move(&world, replay_state);
+            }
+        }
+        run_gameplay_state(&world);
+        run_campaign(&mut world);
+
+        let mut query = world.query::<&Campaign>();
+        let campaign = query.iter().next().unwrap().1;
+        assert!(campaign.won_at.is_some());
+        assert_eq!(campaign.progress, Progress::default());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::components::*;
+use hecs::World;
+use sokoban_core::solver::{board_from_world, Solver, SolverStatus};
+
+/// Starts looking for the next push from the current position, the search
+/// itself runs in `run_hint`.
+pub fn request_hint(world: &World) {
+    let (_, state) = board_from_world(world);
+
+    let mut query = world.query::<&mut Hint>();
+    let hint = query.iter().next().unwrap().1;
+    *hint = Hint {
+        status: HintStatus::Searching,
+        search: Some(Search::new(Solver::from_world(world))),
+        boxes: state.boxes,
+    };
+}
+
+pub fn run_hint(world: &World) {
+    let mut query = world.query::<&mut Hint>();
+    let hint = query.iter().next().unwrap().1;
+
+    if matches!(hint.status, HintStatus::None) {
+        return;
+    }
+
+    // Walking around doesn't change the next push, moving a box does
+    let (_, state) = board_from_world(world);
+    if state.boxes != hint.boxes {
+        *hint = Hint::default();
+        return;
+    }
+
+    let Some(search) = hint.search.as_mut() else {
+        return;
+    };
+
+    hint.status = match search.run_frame() {
+        SolverStatus::Searching if !search.is_out_of_time() => return,
+        SolverStatus::Searching => HintStatus::NotFound,
+        SolverStatus::Solved(solution) => match solution.pushes.first() {
+            Some(push) => HintStatus::Push(*push),
+            // Already solved, nothing to hint at
+            None => HintStatus::None,
+        },
+        SolverStatus::Unsolvable => HintStatus::Unsolvable,
+    };
+    hint.search = None;
+}
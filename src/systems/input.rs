@@ -1,112 +1,137 @@
 use crate::components::*;
 use crate::constants::*;
-use crate::events::*;
-use hecs::{Entity, World};
+use crate::controls::Action;
+use crate::gamepad::Gamepads;
+use crate::settings::Settings;
+use crate::systems::{hint, replay, touch, walk};
+use hecs::World;
 use macroquad::input;
-use macroquad::input::KeyCode;
-use std::collections::HashMap;
+use macroquad::input::MouseButton;
+use macroquad::time::get_frame_time;
+use sokoban_core::systems::movement::{is_push, redo_move, take_step, undo_move};
+use std::time::Duration;
+
+const MOVES: [(Action, Direction); 4] = [
+    (Action::MoveUp, Direction::Up),
+    (Action::MoveDown, Direction::Down),
+    (Action::MoveLeft, Direction::Left),
+    (Action::MoveRight, Direction::Right),
+];
+
+/// Reads the gamepads once a frame, before anything checks their buttons.
+pub fn run_gamepads(world: &World) {
+    let mut query = world.query::<&mut Gamepads>();
+    query.iter().next().unwrap().1.update();
+}
 
 pub fn run_input(world: &World) {
-    let mut to_move: Vec<(Entity, KeyCode)> = Vec::new();
-    let mut events = Vec::new();
-
-    // get all the movables and immovables
-    let mov: HashMap<(u8, u8), Entity> = world
-        .query::<(&Position, &Movable)>()
-        .iter()
-        .map(|t| ((t.1 .0.x, t.1 .0.y), t.0))
-        .collect::<HashMap<_, _>>();
-    let immov: HashMap<(u8, u8), Entity> = world
-        .query::<(&Position, &Immovable)>()
-        .iter()
-        .map(|t| ((t.1 .0.x, t.1 .0.y), t.0))
-        .collect::<HashMap<_, _>>();
-
-    for (_, (position, _player)) in world.query::<(&mut Position, &Player)>().iter() {
-        // Now iterate through current position to the end of the map
-        // on the correct axis and check what needs to move.
-        let key = if input::is_key_pressed(KeyCode::Up) {
-            KeyCode::Up
-        } else if input::is_key_pressed(KeyCode::Down) {
-            KeyCode::Down
-        } else if input::is_key_pressed(KeyCode::Left) {
-            KeyCode::Left
-        } else if input::is_key_pressed(KeyCode::Right) {
-            KeyCode::Right
-        } else {
-            continue;
-        };
-
-        let (start, end, is_x) = match key {
-            KeyCode::Up => (position.y, 0, false),
-            KeyCode::Down => (position.y, MAP_HEIGHT - 1, false),
-            KeyCode::Left => (position.x, 0, true),
-            KeyCode::Right => (position.x, MAP_WIDTH - 1, true),
-            _ => continue,
-        };
-
-        let range = if start < end {
-            (start..=end).collect::<Vec<_>>()
-        } else {
-            (end..=start).rev().collect::<Vec<_>>()
-        };
-
-        for x_or_y in range {
-            let pos = if is_x {
-                (x_or_y, position.y)
-            } else {
-                (position.x, x_or_y)
-            };
-
-            // find a movable
-            // if it exists, we try to move it and continue
-            // if it doesn't exist, we continue and try to find an immovable instead
-            match mov.get(&pos) {
-                Some(entity) => to_move.push((*entity, key)),
-                None => {
-                    // find an immovable
-                    // if it exists, we need to stop and not move anything
-                    // if it doesn't exist, we stop because we found a gap
-                    match immov.get(&pos) {
-                        Some(_id) => {
-                            to_move.clear();
-                            events.push(Event::PlayerHitObstacle {});
-                            break;
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
+    // A replay takes over the player's moves until it ends or is stopped
+    if world.query::<&Replay>().iter().next().unwrap().1.active {
+        replay::run_replay(world);
+        return;
+    }
+
+    if touch::run_touch(world) {
+        return;
     }
 
-    // Update gameplay moves
-    if !to_move.is_empty() {
-        let mut query = world.query::<&mut Gameplay>();
-        let gameplay = query.iter().next().unwrap().1;
-        gameplay.moves_count += 1;
+    // Clicking a tile walks the player there and dragging a box pushes it,
+    // until the walk is done or a key is pressed
+    if input::is_mouse_button_pressed(MouseButton::Left) {
+        walk::start_walk(world, input::mouse_position());
+        return;
     }
+    if input::is_mouse_button_released(MouseButton::Left) {
+        walk::drop_box(world, input::mouse_position());
+        return;
+    }
+    if input::get_last_key_pressed().is_none() && walk::run_walk(world) {
+        return;
+    }
+    walk::stop_walk(world);
+
+    let mut query = world.query::<&Settings>();
+    let settings = query.iter().next().unwrap().1;
 
-    // Now actually move what needs to be moved
-    for (entity, key) in to_move {
-        let mut position = world.get::<&mut Position>(entity).unwrap();
+    let mut query = world.query::<&Gamepads>();
+    let gamepads = query.iter().next().unwrap().1;
+
+    let is_pressed =
+        |action| settings.key_bindings.is_pressed(action) || gamepads.is_pressed(action);
+
+    if is_pressed(Action::Replay) {
+        replay::start_replay(world);
+        return;
+    }
+    if is_pressed(Action::Solve) {
+        replay::start_solver_replay(world);
+        return;
+    }
+    if is_pressed(Action::Hint) {
+        hint::request_hint(world);
+        return;
+    }
+    if is_pressed(Action::Restart) {
+        replay::restart_level(world);
+        return;
+    }
+    if is_pressed(Action::Undo) {
+        undo_move(world);
+        return;
+    }
+    if is_pressed(Action::Redo) {
+        redo_move(world);
+        return;
+    }
 
-        match key {
-            KeyCode::Up => position.y -= 1,
-            KeyCode::Down => position.y += 1,
-            KeyCode::Left => position.x -= 1,
-            KeyCode::Right => position.x += 1,
-            _ => (),
+    let Some((action, direction)) = MOVES.into_iter().find(|(action, _)| is_pressed(*action))
+    else {
+        if let Some(direction) = repeated_step(world, settings, gamepads) {
+            take_step(world, direction);
         }
+        return;
+    };
+
+    // Holding the key down takes the step again after a while
+    let mut query = world.query::<&mut KeyRepeat>();
+    *query.iter().next().unwrap().1 = KeyRepeat {
+        action: Some(action),
+        held_for: Duration::ZERO,
+        next_step_at: settings.repeat_delay,
+    };
 
-        // Fire an event for the entity that just moved
-        events.push(Event::EntityMoved(EntityMoved { entity }));
+    take_step(world, direction);
+}
+
+/// The step to take again this frame for the move key, gamepad button or
+/// stick direction held down, if any.
+fn repeated_step(world: &World, settings: &Settings, gamepads: &Gamepads) -> Option<Direction> {
+    let mut query = world.query::<&mut KeyRepeat>();
+    let key_repeat = query.iter().next().unwrap().1;
+
+    let action = key_repeat.action?;
+    let is_down = settings.key_bindings.is_down(action) || gamepads.is_down(action);
+    if !is_down || settings.repeat_rate <= 0.0 {
+        key_repeat.action = None;
+        return None;
     }
 
-    // Finally add events back into the world
-    {
-        let mut query = world.query::<&mut EventQueue>();
-        let event_queue = query.iter().next().unwrap().1;
-        event_queue.events.append(&mut events);
+    key_repeat.held_for += Duration::from_secs_f32(get_frame_time());
+    if key_repeat.held_for < key_repeat.next_step_at {
+        return None;
+    }
+
+    // Never faster than the player walks to a clicked tile
+    let interval = Duration::from_secs_f32(1.0 / settings.repeat_rate)
+        .max(WALK_STEP_TIME.div_f32(settings.animation_speed));
+    key_repeat.next_step_at += interval;
+
+    let (_, direction) = MOVES
+        .into_iter()
+        .find(|(move_action, _)| *move_action == action)?;
+    if settings.repeat_stops_at_push && is_push(world, direction) {
+        key_repeat.action = None;
+        return None;
     }
+    Some(direction)
 }
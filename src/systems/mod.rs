@@ -1,4 +1,10 @@
+pub mod campaign;
 pub mod events;
-pub mod gameplay;
+pub mod hint;
 pub mod input;
+pub mod options;
 pub mod rendering;
+pub mod replay;
+pub mod save;
+pub mod touch;
+pub mod walk;
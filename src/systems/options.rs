@@ -0,0 +1,245 @@
+use crate::components::*;
+use crate::controls::*;
+use crate::settings::*;
+use hecs::World;
+use macroquad::input;
+use macroquad::input::KeyCode;
+use macroquad::texture::{FilterMode, Texture2D};
+use macroquad::window;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const WINDOW_SIZES: [(i32, i32); 4] = [(640, 480), (800, 600), (1024, 768), (1280, 960)];
+const VOLUME_STEP: f32 = 0.1;
+const ANIMATION_SPEEDS: [f32; 5] = [0.5, 1.0, 1.5, 2.0, 3.0];
+const REPEAT_DELAYS: [Duration; 4] = [
+    Duration::from_millis(150),
+    Duration::from_millis(250),
+    Duration::from_millis(400),
+    Duration::from_millis(600),
+];
+// Steps per second, 0 being no repeat
+const REPEAT_RATES: [f32; 5] = [0.0, 5.0, 10.0, 15.0, 20.0];
+
+/// A line of the options menu.
+#[derive(Clone, Copy)]
+pub enum OptionEntry {
+    WindowSize,
+    Fullscreen,
+    FilterMode,
+    Volume,
+    AnimationSpeed,
+    RepeatDelay,
+    RepeatRate,
+    RepeatStopsAtPush,
+    TouchButtons,
+    KeyPreset,
+    Key(Action),
+}
+
+pub fn option_entries() -> Vec<OptionEntry> {
+    let mut entries = vec![
+        OptionEntry::WindowSize,
+        OptionEntry::Fullscreen,
+        OptionEntry::FilterMode,
+        OptionEntry::Volume,
+        OptionEntry::AnimationSpeed,
+        OptionEntry::RepeatDelay,
+        OptionEntry::RepeatRate,
+        OptionEntry::RepeatStopsAtPush,
+        OptionEntry::TouchButtons,
+        OptionEntry::KeyPreset,
+    ];
+    entries.extend(Action::ALL.map(OptionEntry::Key));
+    entries
+}
+
+impl OptionEntry {
+    pub fn label(self) -> &'static str {
+        match self {
+            OptionEntry::WindowSize => "Window size",
+            OptionEntry::Fullscreen => "Fullscreen",
+            OptionEntry::FilterMode => "Filter mode",
+            OptionEntry::Volume => "Volume",
+            OptionEntry::AnimationSpeed => "Animation speed",
+            OptionEntry::RepeatDelay => "Key repeat delay",
+            OptionEntry::RepeatRate => "Key repeat rate",
+            OptionEntry::RepeatStopsAtPush => "Stop repeat at boxes",
+            OptionEntry::TouchButtons => "Touch buttons",
+            OptionEntry::KeyPreset => "Keys",
+            OptionEntry::Key(action) => action.label(),
+        }
+    }
+
+    pub fn value(self, settings: &Settings) -> String {
+        match self {
+            OptionEntry::WindowSize => {
+                format!("{}x{}", settings.window_width, settings.window_height)
+            }
+            OptionEntry::Fullscreen => String::from(if settings.fullscreen { "on" } else { "off" }),
+            OptionEntry::FilterMode => filter_mode_name(settings.filter_mode).to_string(),
+            OptionEntry::Volume => format!("{:.0}%", settings.volume * 100.0),
+            OptionEntry::AnimationSpeed => format!("x{}", settings.animation_speed),
+            OptionEntry::RepeatDelay => format!("{} ms", settings.repeat_delay.as_millis()),
+            OptionEntry::RepeatRate if settings.repeat_rate == 0.0 => String::from("off"),
+            OptionEntry::RepeatRate => format!("{} steps/s", settings.repeat_rate),
+            OptionEntry::RepeatStopsAtPush => String::from(if settings.repeat_stops_at_push {
+                "on"
+            } else {
+                "off"
+            }),
+            OptionEntry::TouchButtons => {
+                String::from(if settings.touch_buttons { "on" } else { "off" })
+            }
+            OptionEntry::KeyPreset => settings.key_bindings.preset.name().to_string(),
+            OptionEntry::Key(action) => settings.key_bindings.describe(action),
+        }
+    }
+}
+
+/// Moves through the options menu and changes the settings, applying them
+/// straight away. They are saved when leaving the menu.
+pub fn run_options_input(world: &World, texture_atlas: &HashMap<String, Texture2D>) {
+    let mut query = world.query::<&mut OptionsMenu>();
+    let menu = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&mut Settings>();
+    let settings = query.iter().next().unwrap().1;
+
+    let entries = option_entries();
+    let entry = entries[menu.selected];
+
+    if menu.rebinding {
+        if let OptionEntry::Key(action) = entry {
+            match input::get_last_key_pressed() {
+                Some(KeyCode::Escape) => {
+                    menu.rebinding = false;
+                    menu.conflict = None;
+                }
+                // A key another action reads first would never reach this one
+                Some(key) if BINDABLE_KEYS.contains(&key) => {
+                    match settings.key_bindings.conflict(action, key) {
+                        Some(other) => menu.conflict = Some((key, other)),
+                        None => {
+                            settings.key_bindings.overrides.insert(action, vec![key]);
+                            menu.rebinding = false;
+                            menu.conflict = None;
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        return;
+    }
+
+    if input::is_key_pressed(KeyCode::Escape) {
+        save_settings(settings);
+
+        let mut query = world.query::<&mut Campaign>();
+        query.iter().next().unwrap().1.screen = Screen::LevelSelect;
+        return;
+    }
+
+    if input::is_key_pressed(KeyCode::Up) && menu.selected > 0 {
+        menu.selected -= 1;
+        return;
+    }
+    if input::is_key_pressed(KeyCode::Down) && menu.selected + 1 < entries.len() {
+        menu.selected += 1;
+        return;
+    }
+
+    let change = if input::is_key_pressed(KeyCode::Left) {
+        -1
+    } else if input::is_key_pressed(KeyCode::Right) {
+        1
+    } else if input::is_key_pressed(KeyCode::Enter) {
+        0
+    } else {
+        return;
+    };
+
+    match entry {
+        OptionEntry::WindowSize => {
+            let current = WINDOW_SIZES
+                .iter()
+                .position(|size| *size == (settings.window_width, settings.window_height))
+                .unwrap_or(0);
+            let next = (current as i32 + change).clamp(0, WINDOW_SIZES.len() as i32 - 1);
+            (settings.window_width, settings.window_height) = WINDOW_SIZES[next as usize];
+            apply_window_size(settings);
+        }
+        OptionEntry::Fullscreen => {
+            settings.fullscreen = !settings.fullscreen;
+            apply_window_size(settings);
+        }
+        OptionEntry::FilterMode => {
+            settings.filter_mode = match settings.filter_mode {
+                FilterMode::Nearest => FilterMode::Linear,
+                FilterMode::Linear => FilterMode::Nearest,
+            };
+            for texture in texture_atlas.values() {
+                texture.set_filter(settings.filter_mode);
+            }
+        }
+        OptionEntry::Volume => {
+            let volume = settings.volume + change as f32 * VOLUME_STEP;
+            // Rounded so the steps don't drift away from whole percents
+            settings.volume = ((volume * 10.0).round() / 10.0).clamp(0.0, 1.0);
+        }
+        OptionEntry::AnimationSpeed => {
+            let current = ANIMATION_SPEEDS
+                .iter()
+                .position(|speed| *speed == settings.animation_speed)
+                .unwrap_or(1);
+            let next = (current as i32 + change).clamp(0, ANIMATION_SPEEDS.len() as i32 - 1);
+            settings.animation_speed =
+                clamp_animation_speed(ANIMATION_SPEEDS[next as usize]).unwrap();
+        }
+        OptionEntry::RepeatDelay => {
+            let current = REPEAT_DELAYS
+                .iter()
+                .position(|delay| *delay == settings.repeat_delay)
+                .unwrap_or(1);
+            let next = (current as i32 + change).clamp(0, REPEAT_DELAYS.len() as i32 - 1);
+            settings.repeat_delay = REPEAT_DELAYS[next as usize];
+        }
+        OptionEntry::RepeatRate => {
+            let current = REPEAT_RATES
+                .iter()
+                .position(|rate| *rate == settings.repeat_rate)
+                .unwrap_or(2);
+            let next = (current as i32 + change).clamp(0, REPEAT_RATES.len() as i32 - 1);
+            settings.repeat_rate = clamp_repeat_rate(REPEAT_RATES[next as usize]).unwrap();
+        }
+        OptionEntry::RepeatStopsAtPush => {
+            settings.repeat_stops_at_push = !settings.repeat_stops_at_push
+        }
+        OptionEntry::TouchButtons => settings.touch_buttons = !settings.touch_buttons,
+        OptionEntry::KeyPreset => {
+            let current = KeyPreset::ALL
+                .iter()
+                .position(|preset| *preset == settings.key_bindings.preset)
+                .unwrap();
+            let next = (current as i32 + change).rem_euclid(KeyPreset::ALL.len() as i32);
+            settings.key_bindings.preset = KeyPreset::ALL[next as usize];
+        }
+        // Enter picks a key for the action, left and right go back to the
+        // preset's keys
+        OptionEntry::Key(_) if change == 0 => menu.rebinding = true,
+        OptionEntry::Key(action) => {
+            settings.key_bindings.overrides.remove(&action);
+        }
+    }
+}
+
+fn apply_window_size(settings: &Settings) {
+    window::set_fullscreen(settings.fullscreen);
+    if !settings.fullscreen {
+        window::request_new_screen_size(
+            settings.window_width as f32,
+            settings.window_height as f32,
+        );
+    }
+}
@@ -1,11 +1,28 @@
 use crate::components::*;
 use crate::constants::*;
+use crate::controls::{key_name, Action};
+use crate::settings::Settings;
+use crate::systems::options::option_entries;
+use crate::systems::touch::touch_buttons;
+use crate::systems::walk::tile_at;
 use hecs::{Entity, World};
 use macroquad::prelude::*;
+use std::collections::HashMap;
 use std::time::Duration;
 
+// Width kept free on the right of the map for the game text
+const TEXT_PANEL_WIDTH: f32 = 115.0;
+
+/// Size to draw the tiles at so the whole level fits left of the text
+/// panel, never larger than the textures themselves.
+pub fn tile_size(map_size: &MapSize) -> f32 {
+    let width = (screen_width() - TEXT_PANEL_WIDTH) / map_size.width.max(1) as f32;
+    let height = screen_height() / map_size.height.max(1) as f32;
+    TILE_WIDTH.min(width).min(height).floor().max(1.0)
+}
+
 // ANCHOR: run_rendering
-pub fn run_rendering(world: &World) {
+pub fn run_rendering(world: &World, texture_atlas: &HashMap<String, Texture2D>) {
     // Clearing the screen (this gives us the background colour)
     clear_background(LIGHTGRAY);
 
@@ -13,30 +30,126 @@ pub fn run_rendering(world: &World) {
     let mut query = world.query::<&Time>();
     let time = query.iter().next().unwrap().1;
 
+    let mut query = world.query::<&Settings>();
+    let settings = query.iter().next().unwrap().1;
+    let animation_time = time.delta.mul_f32(settings.animation_speed);
+
     // Get all the renderables with their positions and sort by the position z
     // This will allow us to have entities layered visually.
     let mut query = world.query::<(&Position, &Renderable)>();
     let mut rendering_data: Vec<(Entity, (&Position, &Renderable))> = query.into_iter().collect();
     rendering_data.sort_by_key(|&k| k.1 .0.z);
 
+    // Scale the tiles down when the level is too big to fit on screen
+    let mut query = world.query::<&MapSize>();
+    let tile_width = tile_size(query.iter().next().unwrap().1);
+
+    let mut query = world.query::<&DeadSquares>();
+    let dead_squares = query.iter().next().unwrap().1;
+
     // Iterate each of the renderables, determine which image path should be rendered
     // at which drawparams, and then add that to the rendering_batches.
-    for (_, (position, renderable)) in rendering_data.iter() {
+    for (entity, (position, renderable)) in rendering_data.iter() {
         // Load the image
-        let image = get_image(renderable, time.delta);
-        let x = position.x as f32 * TILE_WIDTH;
-        let y = position.y as f32 * TILE_WIDTH;
+        let image = get_image(texture_atlas, renderable, animation_time);
+        let x = position.x as f32 * tile_width;
+        let y = position.y as f32 * tile_width;
+
+        // Tint the boxes that can't reach a spot anymore
+        let is_stuck = world
+            .get::<&Box>(*entity)
+            .is_ok_and(|the_box| dead_squares.contains(position, the_box.colour));
+        let tint = if is_stuck {
+            Color::new(1.0, 0.45, 0.45, 1.0)
+        } else {
+            WHITE
+        };
+
+        draw_texture_ex(
+            &image,
+            x,
+            y,
+            tint,
+            DrawTextureParams {
+                dest_size: Some(vec2(tile_width, tile_width)),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Highlight the box to push next and the way to push it
+    let mut query = world.query::<&Hint>();
+    let hint = query.iter().next().unwrap().1;
+
+    if let HintStatus::Push(push) = hint.status {
+        let x = push.x as f32 * tile_width;
+        let y = push.y as f32 * tile_width;
+        draw_rectangle_lines(x, y, tile_width, tile_width, 3.0, YELLOW);
 
-        draw_texture(&image, x, y, WHITE);
+        let (dx, dy) = match push.direction {
+            Direction::Up => (0.0, -1.0),
+            Direction::Down => (0.0, 1.0),
+            Direction::Left => (-1.0, 0.0),
+            Direction::Right => (1.0, 0.0),
+        };
+        let center = vec2(x, y) + vec2(tile_width, tile_width) / 2.0;
+        let forward = vec2(dx, dy) * tile_width;
+        let side = vec2(-dy, dx) * tile_width / 4.0;
+        let tip = center + forward;
+        draw_line(center.x, center.y, tip.x, tip.y, 3.0, YELLOW);
+        draw_triangle(
+            tip,
+            tip - forward / 3.0 + side,
+            tip - forward / 3.0 - side,
+            YELLOW,
+        );
     }
 
+    // Outline the box being dragged and where it would be dropped
+    let mut query = world.query::<&Walk>();
+    let walk = query.iter().next().unwrap().1;
+
+    if let Some(the_box) = walk.dragged_box {
+        let mut dragged_tiles = vec![];
+        if let Ok(position) = world.get::<&Position>(the_box) {
+            dragged_tiles.push((position.x, position.y));
+        }
+        dragged_tiles.extend(tile_at(world, mouse_position()));
+
+        for (x, y) in dragged_tiles {
+            draw_rectangle_lines(
+                x as f32 * tile_width,
+                y as f32 * tile_width,
+                tile_width,
+                tile_width,
+                3.0,
+                SKYBLUE,
+            );
+        }
+    }
+
+    let text_x = screen_width() - TEXT_PANEL_WIDTH;
+
     // Render any text
+    let mut query = world.query::<&Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
+    draw_text_ex(
+        campaign.levels[campaign.current_level].name(),
+        text_x,
+        60.0,
+        TextParams {
+            color: Color::new(0.0, 0.0, 0.0, 1.0),
+            ..Default::default()
+        },
+    );
+
     let mut query = world.query::<&Gameplay>();
     let gameplay = query.iter().next().unwrap().1;
 
     draw_text_ex(
         &gameplay.state.to_string(),
-        525.0,
+        text_x,
         80.0,
         TextParams {
             color: Color::new(0.0, 0.0, 0.0, 1.0),
@@ -44,30 +157,324 @@ pub fn run_rendering(world: &World) {
         },
     );
 
+    let elapsed = gameplay.elapsed.as_secs();
+    let stats = [
+        format!("Moves: {}", gameplay.moves_count),
+        format!("Pushes: {}", gameplay.pushes_count),
+        format!("Boxes: {}/{}", gameplay.boxes_on_spot, gameplay.boxes_total),
+        format!("Time: {}:{:02}", elapsed / 60, elapsed % 60),
+    ];
+    for (line, stat) in stats.iter().enumerate() {
+        draw_text_ex(
+            stat,
+            text_x,
+            100.0 + line as f32 * 20.0,
+            TextParams {
+                color: Color::new(0.0, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Render FPS
+    let fps = format!("FPS: {:.0}", get_fps());
     draw_text_ex(
-        &gameplay.moves_count.to_string(),
-        525.0,
-        100.0,
+        &fps,
+        text_x,
+        100.0 + stats.len() as f32 * 20.0,
         TextParams {
             color: Color::new(0.0, 0.0, 0.0, 1.0),
             ..Default::default()
         },
     );
 
-    // Render FPS
-    let fps = format!("FPS: {:.0}", get_fps());
+    // Offer to take back the move that lost the level
+    if matches!(gameplay.state, GameplayState::Lost) {
+        draw_text_ex(
+            &format!(
+                "This position is lost, undo? ({})",
+                key_name(settings.key_bindings.keys(Action::Undo)[0])
+            ),
+            TILE_WIDTH / 2.0,
+            screen_height() - TILE_WIDTH / 2.0,
+            TextParams {
+                color: Color::new(0.7, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Render the hint
+    let hint_status = match hint.status {
+        HintStatus::None => None,
+        HintStatus::Searching => Some(String::from("Hint: thinking")),
+        HintStatus::Push(push) => Some(format!("Hint: {}", push.direction)),
+        HintStatus::Unsolvable => Some(String::from("Unsolvable")),
+        HintStatus::NotFound => Some(String::from("No hint found")),
+    };
+    if let Some(hint_status) = hint_status {
+        draw_text_ex(
+            &hint_status,
+            text_x,
+            140.0 + stats.len() as f32 * 20.0,
+            TextParams {
+                color: Color::new(0.0, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Render the replay progress
+    let mut query = world.query::<&Replay>();
+    let replay = query.iter().next().unwrap().1;
+
+    if replay.active {
+        let replay_status = match &replay.search {
+            Some(search) => format!("Solving... {} positions", search.solver.explored_nodes()),
+            None => format!(
+                "{} {}/{} x{}",
+                if replay.playing { "Replay" } else { "Paused" },
+                replay.next_move,
+                replay.moves.len(),
+                replay.speed / REPLAY_SPEED
+            ),
+        };
+        draw_text_ex(
+            &replay_status,
+            text_x,
+            120.0 + stats.len() as f32 * 20.0,
+            TextParams {
+                color: Color::new(0.0, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Render the on-screen buttons for touch screens
+    for (rect, action) in touch_buttons(world) {
+        draw_rectangle(
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            Color::new(1.0, 1.0, 1.0, 0.6),
+        );
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, DARKGRAY);
+        draw_text_ex(
+            action.label(),
+            rect.x + 10.0,
+            rect.y + rect.h / 2.0 + 6.0,
+            TextParams {
+                color: Color::new(0.0, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+const MAX_ERROR_LINES: usize = 5;
+
+pub fn run_level_select_rendering(world: &World) {
+    clear_background(LIGHTGRAY);
+
+    let mut query = world.query::<&Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
     draw_text_ex(
-        &fps,
-        525.0,
-        120.0,
+        "Select a level",
+        TILE_WIDTH,
+        TILE_WIDTH,
+        TextParams {
+            color: Color::new(0.0, 0.0, 0.0, 1.0),
+            ..Default::default()
+        },
+    );
+
+    // Problems with the last level that failed to load are listed at the
+    // bottom of the screen, a few at most and never more than half of it,
+    // the rest are in the log.
+    let line_height = 24.0;
+    let screen_lines = ((screen_height() - 2.0 * TILE_WIDTH) / line_height) as usize;
+    let error_lines: Vec<String> = campaign
+        .level_errors
+        .iter()
+        .take(MAX_ERROR_LINES.min(screen_lines / 2))
+        .map(|error| error.to_string())
+        .collect();
+    for (line, error) in error_lines.iter().rev().enumerate() {
+        draw_text_ex(
+            error,
+            TILE_WIDTH,
+            screen_height() - TILE_WIDTH - line as f32 * line_height,
+            TextParams {
+                color: Color::new(0.7, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Only show the levels around the selected one so long level packs
+    // still fit on the screen.
+    let visible_lines = screen_lines.saturating_sub(error_lines.len());
+    let first_line = campaign
+        .selected_level
+        .saturating_sub(visible_lines / 2)
+        .min(campaign.levels.len().saturating_sub(visible_lines));
+
+    for (line, (index, level)) in campaign
+        .levels
+        .iter()
+        .enumerate()
+        .skip(first_line)
+        .take(visible_lines)
+        .enumerate()
+    {
+        let status = if let Some(record) = campaign.progress.levels.get(&level.key()) {
+            let best_time = record.best_time.as_secs();
+            format!(
+                "- best {} moves, {} pushes, {}:{:02}",
+                record.best_moves,
+                record.best_pushes,
+                best_time / 60,
+                best_time % 60
+            )
+        } else if campaign.is_unlocked(index) {
+            String::new()
+        } else {
+            String::from("- locked")
+        };
+        let cursor = if index == campaign.selected_level {
+            ">"
+        } else {
+            " "
+        };
+        let color = if campaign.is_unlocked(index) {
+            Color::new(0.0, 0.0, 0.0, 1.0)
+        } else {
+            Color::new(0.4, 0.4, 0.4, 1.0)
+        };
+
+        draw_text_ex(
+            &format!("{} {}. {} {}", cursor, index + 1, level.name(), status),
+            TILE_WIDTH,
+            2.0 * TILE_WIDTH + line as f32 * line_height,
+            TextParams {
+                color,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+pub fn run_resume_rendering(world: &World) {
+    clear_background(LIGHTGRAY);
+
+    let mut query = world.query::<&Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&Settings>();
+    let settings = query.iter().next().unwrap().1;
+
+    let lines = [
+        format!(
+            "Resume {} where you left it?",
+            campaign.levels[campaign.selected_level].name()
+        ),
+        format!(
+            "Enter or tap: resume, {}: start over",
+            settings.key_bindings.describe(Action::Menu)
+        ),
+    ];
+    for (line, text) in lines.iter().enumerate() {
+        draw_text_ex(
+            text,
+            TILE_WIDTH,
+            TILE_WIDTH + line as f32 * 24.0,
+            TextParams {
+                color: Color::new(0.0, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+pub fn run_options_rendering(world: &World) {
+    clear_background(LIGHTGRAY);
+
+    let mut query = world.query::<&OptionsMenu>();
+    let menu = query.iter().next().unwrap().1;
+
+    let mut query = world.query::<&Settings>();
+    let settings = query.iter().next().unwrap().1;
+
+    draw_text_ex(
+        "Options",
+        TILE_WIDTH,
+        TILE_WIDTH,
         TextParams {
             color: Color::new(0.0, 0.0, 0.0, 1.0),
             ..Default::default()
         },
     );
+
+    // The help sits at the bottom of the screen, with as many options above
+    // it as fit, around the selected one
+    let line_height = 24.0;
+    let entries = option_entries();
+    let visible_lines = ((screen_height() - 3.0 * TILE_WIDTH) / line_height) as usize;
+    let first_line = menu
+        .selected
+        .saturating_sub(visible_lines / 2)
+        .min(entries.len().saturating_sub(visible_lines));
+
+    for (line, (index, entry)) in entries
+        .iter()
+        .enumerate()
+        .skip(first_line)
+        .take(visible_lines)
+        .enumerate()
+    {
+        let cursor = if index == menu.selected { ">" } else { " " };
+        let value = if index == menu.selected && menu.rebinding {
+            match menu.conflict {
+                Some((key, other)) => format!(
+                    "{} is taken by {}, press another key",
+                    key_name(key),
+                    other.label()
+                ),
+                None => String::from("press a key"),
+            }
+        } else {
+            entry.value(settings)
+        };
+
+        draw_text_ex(
+            &format!("{} {}: {}", cursor, entry.label(), value),
+            TILE_WIDTH,
+            2.0 * TILE_WIDTH + line as f32 * line_height,
+            TextParams {
+                color: Color::new(0.0, 0.0, 0.0, 1.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    draw_text_ex(
+        "Left/Right: change, Enter: pick a key, Escape: save and go back",
+        TILE_WIDTH,
+        screen_height() - TILE_WIDTH / 2.0,
+        TextParams {
+            color: Color::new(0.4, 0.4, 0.4, 1.0),
+            ..Default::default()
+        },
+    );
 }
 
-pub fn get_image(renderable: &Renderable, delta: Duration) -> Texture2D {
+pub fn get_image(
+    texture_atlas: &HashMap<String, Texture2D>,
+    renderable: &Renderable,
+    delta: Duration,
+) -> Texture2D {
     let path_index = match renderable.kind() {
         RenderableKind::Static => {
             // We only have one image, so we just return that
@@ -83,5 +490,9 @@ pub fn get_image(renderable: &Renderable, delta: Duration) -> Texture2D {
         }
     };
 
-    renderable.path(path_index)
+    let path = renderable.path(path_index);
+    texture_atlas
+        .get(path)
+        .unwrap_or_else(|| panic!("failed to load {} texture", path))
+        .clone()
 }
@@ -0,0 +1,171 @@
+use crate::components::*;
+use crate::constants::*;
+use crate::controls::Action;
+use crate::settings::Settings;
+use crate::solution;
+use hecs::World;
+use macroquad::time::get_frame_time;
+use sokoban_core::lurd;
+use sokoban_core::map;
+use sokoban_core::solver::{Solver, SolverStatus};
+use sokoban_core::systems::movement::take_step;
+use std::time::Duration;
+
+/// Plays back the moves made so far on the current level, or the solution
+/// saved for it when no move was made yet, or else the best one in the
+/// player's progress, starting from the initial layout.
+pub fn start_replay(world: &World) {
+    let (level, moves_log, best_solution) = {
+        let mut query = world.query::<&Campaign>();
+        let campaign = query.iter().next().unwrap().1;
+
+        let mut query = world.query::<&History>();
+        let history = query.iter().next().unwrap().1;
+
+        let level = campaign.levels[campaign.current_level].clone();
+        let best_solution = campaign
+            .progress
+            .levels
+            .get(&level.key())
+            .map(|record| record.best_solution.clone());
+        (level, history.to_lurd(), best_solution)
+    };
+
+    let moves_lurd = if moves_log.is_empty() {
+        solution::load_solution(&level)
+            .or(best_solution)
+            .unwrap_or_default()
+    } else {
+        moves_log
+    };
+
+    let moves = match lurd::parse_lurd(&moves_lurd) {
+        Ok(moves) => moves,
+        Err(c) => {
+            eprintln!("Can't replay {}, {} is not a move", level.name(), c);
+            return;
+        }
+    };
+    if moves.is_empty() {
+        return;
+    }
+
+    map::restart_level(world);
+
+    let mut query = world.query::<&mut Replay>();
+    let replay = query.iter().next().unwrap().1;
+    *replay = Replay {
+        moves,
+        active: true,
+        playing: true,
+        ..Default::default()
+    };
+}
+
+/// Solves the level from the current position and plays the solution back
+/// from there. The search runs in `run_replay`, the player's input waits
+/// until it's done.
+pub fn start_solver_replay(world: &World) {
+    let solver = Solver::from_world(world);
+
+    let mut query = world.query::<&mut Replay>();
+    let replay = query.iter().next().unwrap().1;
+    *replay = Replay {
+        active: true,
+        search: Some(Search::new(solver)),
+        assisted: replay.assisted,
+        ..Default::default()
+    };
+}
+
+/// Starts the level over for the player, a win from there is theirs again.
+pub fn restart_level(world: &World) {
+    map::restart_level(world);
+
+    let mut query = world.query::<&mut Replay>();
+    query.iter().next().unwrap().1.assisted = false;
+}
+
+pub fn run_replay(world: &World) {
+    let mut query = world.query::<&Settings>();
+    let key_bindings = &query.iter().next().unwrap().1.key_bindings;
+
+    let mut query = world.query::<&mut Replay>();
+    let replay = query.iter().next().unwrap().1;
+
+    // Playback controls, stopping also gives up on a search. The moves
+    // played so far stay on the board, and so does the fact they were played.
+    if key_bindings.is_pressed(Action::ReplayStop) {
+        *replay = Replay {
+            assisted: replay.assisted,
+            ..Default::default()
+        };
+        return;
+    }
+
+    if let Some(search) = replay.search.as_mut() {
+        match search.run_frame() {
+            SolverStatus::Searching if !search.is_out_of_time() => return,
+            SolverStatus::Searching => {
+                eprintln!(
+                    "No solution found after looking at {} positions",
+                    search.solver.explored_nodes()
+                );
+                replay.active = false;
+            }
+            SolverStatus::Unsolvable => {
+                eprintln!("The level can't be solved from here");
+                replay.active = false;
+            }
+            SolverStatus::Solved(solution) => {
+                replay.moves = lurd::parse_lurd(&solution.lurd).unwrap();
+                replay.playing = true;
+                // Already solved, nothing to play
+                replay.active = !replay.moves.is_empty();
+            }
+        }
+        replay.search = None;
+        return;
+    }
+    if key_bindings.is_pressed(Action::ReplayPause) {
+        replay.playing = !replay.playing;
+    }
+    if key_bindings.is_pressed(Action::ReplayFaster) {
+        replay.speed = (replay.speed * 2.0).min(MAX_REPLAY_SPEED);
+    }
+    if key_bindings.is_pressed(Action::ReplaySlower) {
+        replay.speed = (replay.speed / 2.0).max(MIN_REPLAY_SPEED);
+    }
+
+    // Move on when it's time for the next move, or when stepping through
+    // the moves one at a time while paused
+    let take_next_move = if replay.playing {
+        replay.since_last_move += Duration::from_secs_f32(get_frame_time());
+        replay.since_last_move.as_secs_f32() >= 1.0 / replay.speed
+    } else {
+        key_bindings.is_pressed(Action::ReplayStep)
+    };
+    if !take_next_move {
+        return;
+    }
+
+    replay.since_last_move = Duration::ZERO;
+    play_next_move(world, replay);
+}
+
+/// Takes the next move of the replay, on the player's behalf.
+pub(crate) fn play_next_move(world: &World, replay: &mut Replay) {
+    let direction = replay.moves[replay.next_move];
+    replay.next_move += 1;
+    replay.assisted = true;
+
+    if !take_step(world, direction) {
+        eprintln!("Replay stopped, move {} can't be made", replay.next_move);
+        replay.active = false;
+    }
+
+    // Hand control back to the player once every move has been played
+    if replay.next_move >= replay.moves.len() {
+        replay.active = false;
+    }
+}
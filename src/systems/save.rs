@@ -0,0 +1,78 @@
+use crate::components::*;
+use crate::storage;
+use hecs::World;
+use sokoban_core::progress::Progress;
+use sokoban_core::save::{save_level, SavedLevel};
+
+// Storage keys of the level in progress and of the levels solved
+const SAVE_KEY: &str = "level";
+const PROGRESS_KEY: &str = "progress";
+
+/// Reads the levels solved in earlier runs. Progress that can't be read is
+/// started over rather than stopping the game.
+pub fn read_progress() -> Progress {
+    let Some(text) = storage::read(PROGRESS_KEY) else {
+        return Progress::default();
+    };
+
+    Progress::from_text(&text).unwrap_or_else(|error| {
+        eprintln!("Starting with no progress: {}", error);
+        Progress::default()
+    })
+}
+
+pub fn write_progress(progress: &Progress) {
+    storage::write(PROGRESS_KEY, &progress.to_text());
+}
+
+/// Offers to resume the level saved last time, if there is one.
+pub fn offer_resume(world: &World) {
+    let Some(text) = storage::read(SAVE_KEY) else {
+        return;
+    };
+
+    let saved = match SavedLevel::from_text(&text) {
+        Ok(saved) => saved,
+        Err(error) => {
+            // A save that can't be read would be offered again every time
+            eprintln!("Ignoring saved level: {}", error);
+            storage::remove(SAVE_KEY);
+            return;
+        }
+    };
+
+    let mut query = world.query::<&mut Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
+    let Some(level) = campaign.levels.iter().position(|level| saved.is_for(level)) else {
+        return;
+    };
+
+    campaign.selected_level = level;
+    campaign.screen = Screen::Resume;
+    campaign.level_to_load = None;
+    campaign.resume = Some(saved);
+}
+
+/// Saves the level being played whenever a step was taken or undone, a won
+/// level has nothing left to resume.
+pub fn run_autosave(world: &World) {
+    let mut query = world.query::<&mut Campaign>();
+    let campaign = query.iter().next().unwrap().1;
+
+    let steps = world.query::<&History>().iter().next().unwrap().1.to_lurd();
+    if campaign.saved_steps.as_ref() == Some(&steps) {
+        return;
+    }
+
+    let mut query = world.query::<&Gameplay>();
+    let gameplay = query.iter().next().unwrap().1;
+
+    if matches!(gameplay.state, GameplayState::Won) {
+        storage::remove(SAVE_KEY);
+    } else {
+        let level = &campaign.levels[campaign.current_level];
+        storage::write(SAVE_KEY, &save_level(world, level).to_text());
+    }
+    campaign.saved_steps = Some(steps);
+}
@@ -0,0 +1,113 @@
+use crate::components::*;
+use crate::controls::Action;
+use crate::settings::Settings;
+use crate::systems::{replay, walk};
+use hecs::World;
+use macroquad::input::{touches, TouchPhase};
+use macroquad::math::{vec2, Rect, Vec2};
+use macroquad::window::{screen_height, screen_width};
+use sokoban_core::systems::movement::{take_step, undo_move};
+
+// Shortest swipe that moves the player, shorter ones are taps
+const SWIPE_MIN_DISTANCE: f32 = 30.0;
+const TOUCH_BUTTON_SIZE: Vec2 = vec2(100.0, 40.0);
+const TOUCH_BUTTON_MARGIN: f32 = 8.0;
+
+/// The on-screen buttons, in the bottom right corner under the game text.
+/// There are none until the screen has been touched, or when they are
+/// turned off in the settings.
+pub fn touch_buttons(world: &World) -> Vec<(Rect, Action)> {
+    let mut query = world.query::<&TouchControls>();
+    let touch_active = query.iter().next().unwrap().1.active;
+
+    let mut query = world.query::<&Settings>();
+    let buttons_enabled = query.iter().next().unwrap().1.touch_buttons;
+
+    if !touch_active || !buttons_enabled {
+        return vec![];
+    }
+
+    [Action::Restart, Action::Undo]
+        .into_iter()
+        .enumerate()
+        .map(|(index, action)| {
+            let position = vec2(screen_width(), screen_height())
+                - (TOUCH_BUTTON_SIZE + vec2(TOUCH_BUTTON_MARGIN, TOUCH_BUTTON_MARGIN))
+                    * vec2(1.0, index as f32 + 1.0);
+            let rect = Rect::new(
+                position.x,
+                position.y,
+                TOUCH_BUTTON_SIZE.x,
+                TOUCH_BUTTON_SIZE.y,
+            );
+            (rect, action)
+        })
+        .collect()
+}
+
+/// Swipes move the player one step, taps walk it to the tapped tile or press
+/// the on-screen buttons. Returns whether a touch did something this frame.
+pub fn run_touch(world: &World) -> bool {
+    let mut gesture = None;
+    {
+        let mut query = world.query::<&mut TouchControls>();
+        let touch_controls = query.iter().next().unwrap().1;
+
+        for touch in touches() {
+            touch_controls.active = true;
+            match touch.phase {
+                // Only the first finger down is followed
+                TouchPhase::Started if touch_controls.swipe.is_none() => {
+                    touch_controls.swipe = Some((touch.id, touch.position));
+                }
+                TouchPhase::Ended if touch_controls.is_followed(touch.id) => {
+                    gesture = touch_controls
+                        .swipe
+                        .take()
+                        .map(|(_, start)| (start, touch.position));
+                }
+                // A tap short enough to start and end between two frames
+                TouchPhase::Ended if touch_controls.swipe.is_none() => {
+                    gesture = Some((touch.position, touch.position));
+                }
+                TouchPhase::Cancelled if touch_controls.is_followed(touch.id) => {
+                    touch_controls.swipe = None;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let Some((start, end)) = gesture else {
+        return false;
+    };
+    walk::stop_walk(world);
+
+    let swipe = end - start;
+    if swipe.length() < SWIPE_MIN_DISTANCE {
+        let pressed_button = touch_buttons(world)
+            .into_iter()
+            .find(|(rect, _)| rect.contains(end))
+            .map(|(_, action)| action);
+        match pressed_button {
+            Some(Action::Undo) => undo_move(world),
+            Some(Action::Restart) => replay::restart_level(world),
+            _ => walk::walk_to(world, (end.x, end.y)),
+        }
+        return true;
+    }
+
+    let direction = if swipe.x.abs() > swipe.y.abs() {
+        if swipe.x > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    } else if swipe.y > 0.0 {
+        Direction::Down
+    } else {
+        Direction::Up
+    };
+    take_step(world, direction);
+    true
+}
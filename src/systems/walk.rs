@@ -0,0 +1,125 @@
+use crate::components::*;
+use crate::constants::*;
+use crate::settings::Settings;
+use crate::systems::rendering::tile_size;
+use hecs::World;
+use macroquad::time::get_frame_time;
+use sokoban_core::systems::movement::{find_path, find_push_path, take_grouped_step};
+use std::time::Duration;
+
+/// The tile drawn at `screen_position`, if it is on the map.
+pub fn tile_at(world: &World, (x, y): (f32, f32)) -> Option<(u16, u16)> {
+    let mut query = world.query::<&MapSize>();
+    let map_size = *query.iter().next().unwrap().1;
+
+    let tile_width = tile_size(&map_size);
+    let (tile_x, tile_y) = (x / tile_width, y / tile_width);
+    if tile_x < 0.0
+        || tile_y < 0.0
+        || tile_x >= map_size.width as f32
+        || tile_y >= map_size.height as f32
+    {
+        return None;
+    }
+
+    Some((tile_x as u16, tile_y as u16))
+}
+
+/// Picks up the box drawn at `screen_position` to drag it, or else walks the
+/// player there if it can get there without pushing anything.
+pub fn start_walk(world: &World, screen_position: (f32, f32)) {
+    let Some((x, y)) = tile_at(world, screen_position) else {
+        return;
+    };
+
+    let clicked_box = world
+        .query::<(&Position, &Box)>()
+        .iter()
+        .find(|(_, (position, _))| (position.x, position.y) == (x, y))
+        .map(|(entity, _)| entity);
+
+    if clicked_box.is_some() {
+        let mut query = world.query::<&mut Walk>();
+        query.iter().next().unwrap().1.dragged_box = clicked_box;
+        return;
+    }
+
+    walk_to(world, screen_position);
+}
+
+/// Walks the player to the tile drawn at `screen_position`, if it can get
+/// there without pushing anything.
+pub fn walk_to(world: &World, screen_position: (f32, f32)) {
+    let Some((x, y)) = tile_at(world, screen_position) else {
+        return;
+    };
+    let Some(path) = find_path(world, x, y) else {
+        return;
+    };
+
+    let mut query = world.query::<&mut Walk>();
+    let walk = query.iter().next().unwrap().1;
+    *walk = Walk {
+        steps: path.into(),
+        ..Default::default()
+    };
+}
+
+/// Pushes the box being dragged to the tile drawn at `screen_position`, when
+/// that can be done without moving any other box.
+pub fn drop_box(world: &World, screen_position: (f32, f32)) {
+    let mut query = world.query::<&mut Walk>();
+    let walk = query.iter().next().unwrap().1;
+
+    let Some(the_box) = walk.dragged_box.take() else {
+        return;
+    };
+    let Some((x, y)) = tile_at(world, screen_position) else {
+        return;
+    };
+    let Some(path) = find_push_path(world, the_box, x, y) else {
+        return;
+    };
+
+    *walk = Walk {
+        steps: path.into(),
+        as_one_move: true,
+        ..Default::default()
+    };
+}
+
+/// Takes the next step of the walk when it's time to. Returns whether the
+/// player is still walking.
+pub fn run_walk(world: &World) -> bool {
+    let mut query = world.query::<&mut Walk>();
+    let walk = query.iter().next().unwrap().1;
+
+    if walk.steps.is_empty() {
+        return false;
+    }
+
+    let mut query = world.query::<&Settings>();
+    let step_time = WALK_STEP_TIME.div_f32(query.iter().next().unwrap().1.animation_speed);
+
+    // The first step is taken right away
+    walk.since_last_step += Duration::from_secs_f32(get_frame_time());
+    if walk.steps_taken > 0 && walk.since_last_step < step_time {
+        return true;
+    }
+
+    walk.since_last_step = Duration::ZERO;
+    let direction = walk.steps.pop_front().unwrap();
+    let follows_previous = walk.as_one_move && walk.steps_taken > 0;
+    if take_grouped_step(world, direction, follows_previous) {
+        walk.steps_taken += 1;
+    } else {
+        walk.steps.clear();
+    }
+    true
+}
+
+pub fn stop_walk(world: &World) {
+    let mut query = world.query::<&mut Walk>();
+    let walk = query.iter().next().unwrap().1;
+    walk.steps.clear();
+}
@@ -0,0 +1,26 @@
+// Keeps the game's saves in the browser's local storage, used by
+// src/storage.rs when running as wasm.
+miniquad_add_plugin({
+    name: "sokoban_storage",
+    version: 1,
+    register_plugin: function (importObject) {
+        function read_string(pointer, length) {
+            return new TextDecoder().decode(new Uint8Array(wasm_memory.buffer, pointer, length));
+        }
+
+        importObject.env.sokoban_storage_length = function (key, key_length) {
+            var value = window.localStorage.getItem(read_string(key, key_length));
+            return value === null ? -1 : new TextEncoder().encode(value).length;
+        };
+        importObject.env.sokoban_storage_read = function (key, key_length, value, value_length) {
+            var bytes = new TextEncoder().encode(window.localStorage.getItem(read_string(key, key_length)) || "");
+            new Uint8Array(wasm_memory.buffer, value, value_length).set(bytes.subarray(0, value_length));
+        };
+        importObject.env.sokoban_storage_write = function (key, key_length, value, value_length) {
+            window.localStorage.setItem(read_string(key, key_length), read_string(value, value_length));
+        };
+        importObject.env.sokoban_storage_remove = function (key, key_length) {
+            window.localStorage.removeItem(read_string(key, key_length));
+        };
+    },
+});